@@ -0,0 +1,265 @@
+use crate::*;
+use chrono::Duration;
+use embedded_graphics::{
+    drawable::{Drawable, Pixel},
+    geometry::Point,
+    primitives::{Circle, Primitive},
+    style::PrimitiveStyle,
+    DrawTarget,
+};
+use std::f64::consts::PI;
+
+/// Julian day of the unix epoch
+const UNIX_EPOCH_JD: f64 = 2_440_587.5;
+/// Julian day of the J2000 epoch
+const J2000: f64 = 2_451_545.0;
+/// Average length of a lunar cycle in days
+const SYNODIC_MONTH: f64 = 29.530_588_853;
+/// Julian day of a known new moon, 2000-01-06 18:14 UTC
+const NEW_MOON_JD: f64 = 2_451_550.26;
+
+/// Sun altitude at sunrise/sunset, including refraction and the sun's radius
+const SUNRISE_ALTITUDE: f64 = -0.833;
+/// Sun altitude at the begin and end of civil twilight
+const CIVIL_TWILIGHT_ALTITUDE: f64 = -6.0;
+
+/// Sun times of a day, `None` during polar night or midnight sun
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SunTimes {
+    pub sunrise: Option<DateTime<Utc>>,
+    pub sunset: Option<DateTime<Utc>>,
+    /// begin of civil twilight
+    pub dawn: Option<DateTime<Utc>>,
+    /// end of civil twilight
+    pub dusk: Option<DateTime<Utc>>,
+    /// zero during polar night, a full day for the midnight sun
+    pub day_length: Duration,
+}
+
+impl SunTimes {
+    /// Sunrise equation as used by NOAA, accurate to about a minute
+    pub fn compute(coordinates: Coordinates, date: NaiveDate) -> Self {
+        let noon = julian_day(date.and_hms(12, 0, 0));
+        // mean solar time at the longitude
+        let mean_noon = (noon - J2000 + 0.0008).round() - coordinates.longitude / 360.0;
+        let anomaly = (357.5291 + 0.985_600_28 * mean_noon)
+            .rem_euclid(360.0)
+            .to_radians();
+        let center =
+            1.9148 * anomaly.sin() + 0.02 * (2.0 * anomaly).sin() + 0.0003 * (3.0 * anomaly).sin();
+        let longitude = (anomaly.to_degrees() + center + 180.0 + 102.9372)
+            .rem_euclid(360.0)
+            .to_radians();
+        let transit = J2000 + mean_noon + 0.0053 * anomaly.sin() - 0.0069 * (2.0 * longitude).sin();
+        let declination = (longitude.sin() * 23.4397_f64.to_radians().sin()).asin();
+
+        // cosine of the hour angle between the transit and the sun at that altitude,
+        // below -1 the sun stays above, above 1 below it
+        let cos_hour_angle = |altitude: f64| {
+            let latitude = coordinates.latitude.to_radians();
+            (altitude.to_radians().sin() - latitude.sin() * declination.sin())
+                / (latitude.cos() * declination.cos())
+        };
+        let around_transit = |altitude: f64| {
+            let cos = cos_hour_angle(altitude);
+            if cos.abs() > 1.0 {
+                return (None, None);
+            }
+            let half_day = cos.acos().to_degrees() / 360.0;
+            (
+                Some(from_julian_day(transit - half_day)),
+                Some(from_julian_day(transit + half_day)),
+            )
+        };
+        let (sunrise, sunset) = around_transit(SUNRISE_ALTITUDE);
+        let (dawn, dusk) = around_transit(CIVIL_TWILIGHT_ALTITUDE);
+        let day_length = match (sunrise, sunset) {
+            (Some(sunrise), Some(sunset)) => sunset - sunrise,
+            _ if cos_hour_angle(SUNRISE_ALTITUDE) < -1.0 => Duration::days(1),
+            _ => Duration::zero(),
+        };
+        Self {
+            sunrise,
+            sunset,
+            dawn,
+            dusk,
+            day_length,
+        }
+    }
+}
+
+fn julian_day(time: NaiveDateTime) -> f64 {
+    time.timestamp() as f64 / 86_400.0 + UNIX_EPOCH_JD
+}
+
+fn from_julian_day(jd: f64) -> DateTime<Utc> {
+    Utc.timestamp(((jd - UNIX_EPOCH_JD) * 86_400.0).round() as i64, 0)
+}
+
+/// Position in the lunar cycle, 0 is new moon, 0.5 full moon
+pub fn moon_phase(time: DateTime<Utc>) -> f64 {
+    ((julian_day(time.naive_utc()) - NEW_MOON_JD) / SYNODIC_MONTH).rem_euclid(1.0)
+}
+
+/// Illuminated fraction of the moon's disc
+pub fn moon_illumination(phase: f64) -> f64 {
+    (1.0 - (2.0 * PI * phase).cos()) / 2.0
+}
+
+/// Moon disc with the unlit part in gray or black, waxing is lit on the right
+/// as seen from the northern hemisphere and mirrored on the southern one
+pub fn draw_moon<C: Shade, T: DrawTarget<C>>(
+    display: &mut T,
+    phase: f64,
+    center: Point,
+    radius: i32,
+    southern: bool,
+) {
+    let dark = C::light().unwrap_or_else(C::black);
+    let terminator = (2.0 * PI * phase).cos();
+    for y in -radius..=radius {
+        let half = ((radius * radius - y * y) as f64).sqrt();
+        for x in -radius..=radius {
+            if (x * x + y * y) > radius * radius {
+                continue;
+            }
+            let x_seen = f64::from(if southern { -x } else { x });
+            let lit = if phase < 0.5 {
+                x_seen >= half * terminator
+            } else {
+                x_seen <= -half * terminator
+            };
+            let color = if lit { C::white() } else { dark };
+            let _ = display.draw_pixel(Pixel(center + Point::new(x, y), color));
+        }
+    }
+    let _ = Circle::new(center, radius as u32)
+        .into_styled(PrimitiveStyle::with_stroke(C::black(), 1))
+        .draw(display);
+}
+
+/// Sun times, twilight and the moon from the `COORDINATES`, without any network,
+/// nothing is drawn without them
+pub fn astronomy<C: Shade, T: DrawTarget<C>>(display: &mut T, clock: &dyn Clock) {
+    draw_astronomy(display, clock.now());
+}
+
+pub fn draw_astronomy<C: Shade, T: DrawTarget<C>>(display: &mut T, now: DateTime<Local>) {
+    let coordinates = match *COORDINATES {
+        Some(coordinates) => coordinates,
+        None => return,
+    };
+    let now = now.with_timezone(&*TIMEZONE);
+    let date = now.naive_local().date();
+    let sun = SunTimes::compute(coordinates, date);
+    if let (Some(sunrise), Some(sunset)) = (sun.sunrise, sun.sunset) {
+        draw_sunset(
+            display,
+            sunrise.with_timezone(&*TIMEZONE),
+            sunset.with_timezone(&*TIMEZONE),
+        );
+    }
+    #[cfg(feature = "layout_large")]
+    draw_twilight(display, &sun);
+
+    let phase = moon_phase(now.with_timezone(&Utc));
+    draw_moon(
+        display,
+        phase,
+        moon_position(),
+        moon_radius(),
+        coordinates.latitude < 0.0,
+    );
+}
+
+#[cfg(feature = "layout_large")]
+fn draw_twilight<C: Shade, T: DrawTarget<C>>(display: &mut T, sun: &SunTimes) {
+    let time = |t: Option<DateTime<Utc>>| {
+        t.map_or_else(
+            || "--:--".to_string(),
+            |t| TIME_FORMAT.time(&t.with_timezone(&*TIMEZONE)),
+        )
+    };
+    text_aligned(
+        display,
+        TextFont::F6x8,
+        &format!(
+            "{} - {}  {}:{:02}h",
+            time(sun.dawn),
+            time(sun.dusk),
+            sun.day_length.num_hours(),
+            sun.day_length.num_minutes() % 60
+        ),
+        area((width() / 2 - 80, 18).into(), 160, 8),
+        HAlign::Center,
+        VAlign::Top,
+    );
+}
+
+#[cfg(feature = "layout_large")]
+fn moon_position() -> Point {
+    // right of the clock, left of the date and warnings
+    (width() / 2 + 85, 58).into()
+}
+
+#[cfg(feature = "layout_large")]
+fn moon_radius() -> i32 {
+    14
+}
+
+#[cfg(feature = "layout_small")]
+fn moon_position() -> Point {
+    // below the date
+    (width() - 20, 44).into()
+}
+
+#[cfg(feature = "layout_small")]
+fn moon_radius() -> i32 {
+    8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::Europe::Berlin;
+
+    const TUEBINGEN: Coordinates = Coordinates {
+        latitude: 48.52,
+        longitude: 9.06,
+    };
+
+    /// Local sunrise and sunset in Berlin time
+    fn sun_times(year: i32, month: u32, day: u32) -> (String, String) {
+        let sun = SunTimes::compute(TUEBINGEN, NaiveDate::from_ymd(year, month, day));
+        let local = |t: Option<DateTime<Utc>>| {
+            let t = t.unwrap().with_timezone(&Berlin);
+            assert_eq!(
+                t.naive_local().date(),
+                NaiveDate::from_ymd(year, month, day)
+            );
+            TimeFormat::H24.time(&t)
+        };
+        (local(sun.sunrise), local(sun.sunset))
+    }
+
+    #[test]
+    fn sun_times_at_spring_forward() {
+        assert_eq!(sun_times(2020, 3, 28), ("6:09".into(), "18:47".into()));
+        assert_eq!(sun_times(2020, 3, 29), ("7:07".into(), "19:49".into()));
+    }
+
+    #[test]
+    fn sun_times_at_fall_back() {
+        assert_eq!(sun_times(2020, 10, 24).0, "7:57");
+        assert_eq!(sun_times(2020, 10, 25).0, "6:59");
+    }
+
+    #[test]
+    fn day_length_without_dst() {
+        // the 23 hour day doesn't shorten the daylight
+        let before = SunTimes::compute(TUEBINGEN, NaiveDate::from_ymd(2020, 3, 28));
+        let after = SunTimes::compute(TUEBINGEN, NaiveDate::from_ymd(2020, 3, 29));
+        let longer = after.day_length - before.day_length;
+        assert!(longer > Duration::minutes(2) && longer < Duration::minutes(5));
+    }
+}
@@ -0,0 +1,240 @@
+use crate::*;
+use core::time::Duration;
+
+/// Looks up `SENSOR_<NAME>_<KEY>` first and falls back to the device wide `<KEY>`
+pub(crate) fn sensor_var(name: Option<&str>, key: &str) -> Option<String> {
+    name.and_then(|name| dotenv::var(format!("SENSOR_{}_{}", name.to_uppercase(), key)).ok())
+        .or_else(|| dotenv::var(key).ok())
+}
+
+/// Parses `sensor_var` if it is set, `None` as name reads just the `<KEY>`
+pub(crate) fn try_parse_var<T: core::str::FromStr>(
+    name: Option<&str>,
+    key: &str,
+) -> Result<Option<T>, String>
+where
+    T::Err: core::fmt::Display,
+{
+    sensor_var(name, key)
+        .map(|v| {
+            v.trim()
+                .parse()
+                .map_err(|e| format!("{} '{}': {}", key, v, e))
+        })
+        .transpose()
+}
+
+fn parse_var<T: core::str::FromStr>(name: Option<&str>, key: &str) -> Option<T>
+where
+    T::Err: core::fmt::Display,
+{
+    try_parse_var(name, key).unwrap_or_else(|e| panic!("invalid {}", e))
+}
+
+/// Either a constant offset or a linear correction through two reference points
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Linear {
+    Offset(f32),
+    /// (raw, reference) at a low and a high point
+    TwoPoint((f32, f32), (f32, f32)),
+}
+
+impl Linear {
+    pub fn apply(&self, value: f32) -> f32 {
+        match *self {
+            Linear::Offset(offset) => value + offset,
+            Linear::TwoPoint((raw_low, ref_low), (raw_high, ref_high)) => {
+                ref_low + (value - raw_low) * (ref_high - ref_low) / (raw_high - raw_low)
+            }
+        }
+    }
+
+    /// `<QUANTITY>_CALIBRATION=raw_low:ref_low,raw_high:ref_high` wins over `<QUANTITY>_OFFSET`
+    fn from_env(name: Option<&str>, quantity: &str, default_offset: f32) -> Self {
+        let key = format!("{}_CALIBRATION", quantity);
+        if let Some(points) = sensor_var(name, &key) {
+            return Linear::parse_two_point(&points)
+                .unwrap_or_else(|e| panic!("invalid {} '{}': {}", key, points, e));
+        }
+        Linear::Offset(parse_var(name, &format!("{}_OFFSET", quantity)).unwrap_or(default_offset))
+    }
+
+    fn parse_two_point(points: &str) -> Result<Self, String> {
+        let parse_point = |point: &str| -> Result<(f32, f32), String> {
+            let mut values = point.splitn(2, ':').map(|v| v.trim().parse::<f32>());
+            match (values.next(), values.next()) {
+                (Some(Ok(raw)), Some(Ok(reference))) => Ok((raw, reference)),
+                _ => Err(format!("expected raw:reference, got '{}'", point)),
+            }
+        };
+        let mut points = points.splitn(2, ',');
+        let low = parse_point(points.next().unwrap_or_default())?;
+        let high = parse_point(points.next().unwrap_or_default())?;
+        if (high.0 - low.0).abs() < core::f32::EPSILON {
+            return Err("both raw values are the same".into());
+        }
+        Ok(Linear::TwoPoint(low, high))
+    }
+}
+
+/// Corrections against a reference instrument, applied to every raw reading
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    pub temperature: Linear,
+    pub humidity: Linear,
+    pub pressure: Linear,
+}
+
+impl Calibration {
+    pub fn from_env(name: Option<&str>, kind: &SensorKind) -> Self {
+        // heat of the raspberry pi, used to be hardcoded for the bme680
+        let default_temperature_offset = match kind {
+            SensorKind::Bme680 { .. } if dotenv::var("SELF_HEATING").is_err() => -2.2,
+            _ => 0.0,
+        };
+        Self {
+            temperature: Linear::from_env(name, "TEMPERATURE", default_temperature_offset),
+            humidity: Linear::from_env(name, "HUMIDITY", 0.0),
+            pressure: Linear::from_env(name, "PRESSURE", 0.0),
+        }
+    }
+
+    pub fn apply(&self, reading: Reading) -> Reading {
+        Reading {
+            temperature: self.temperature.apply(reading.temperature),
+            humidity: reading
+                .humidity
+                .map(|h| self.humidity.apply(h).max(0.0).min(100.0)),
+            pressure: reading.pressure.map(|p| self.pressure.apply(p)),
+            gas_resistance: reading.gas_resistance,
+        }
+    }
+}
+
+/// Oversampling (1, 2, 4, 8 or 16; 0 disables the quantity), IIR filter size and gas heater profile
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bme680Config {
+    pub humidity_oversampling: u8,
+    pub pressure_oversampling: u8,
+    pub temperature_oversampling: u8,
+    pub iir_filter: u8,
+    pub heater_temperature: u16,
+    pub heater_duration: Duration,
+    pub ambient_temperature: i8,
+}
+
+impl Default for Bme680Config {
+    fn default() -> Self {
+        Self {
+            humidity_oversampling: 2,
+            pressure_oversampling: 4,
+            temperature_oversampling: 8,
+            iir_filter: 3,
+            heater_temperature: 320,
+            heater_duration: Duration::from_millis(1500),
+            ambient_temperature: 25,
+        }
+    }
+}
+
+impl Bme680Config {
+    /// `BME680_<QUANTITY>_OVERSAMPLING`, `BME680_IIR_FILTER`, `BME680_HEATER=<°C>:<ms>`
+    /// and `BME680_AMBIENT_TEMPERATURE`
+    pub fn from_env(name: Option<&str>) -> Self {
+        let default = Self::default();
+        let (heater_temperature, heater_duration) = match sensor_var(name, "BME680_HEATER") {
+            Some(heater) => {
+                let mut parts = heater.splitn(2, ':').map(|v| v.trim().parse::<u16>());
+                match (parts.next(), parts.next()) {
+                    (Some(Ok(temp)), Some(Ok(ms))) => (temp, Duration::from_millis(ms as u64)),
+                    _ => panic!("invalid BME680_HEATER '{}', expected <°C>:<ms>", heater),
+                }
+            }
+            None => (default.heater_temperature, default.heater_duration),
+        };
+        let config = Self {
+            humidity_oversampling: parse_var(name, "BME680_HUMIDITY_OVERSAMPLING")
+                .unwrap_or(default.humidity_oversampling),
+            pressure_oversampling: parse_var(name, "BME680_PRESSURE_OVERSAMPLING")
+                .unwrap_or(default.pressure_oversampling),
+            temperature_oversampling: parse_var(name, "BME680_TEMPERATURE_OVERSAMPLING")
+                .unwrap_or(default.temperature_oversampling),
+            iir_filter: parse_var(name, "BME680_IIR_FILTER").unwrap_or(default.iir_filter),
+            heater_temperature,
+            heater_duration,
+            ambient_temperature: parse_var(name, "BME680_AMBIENT_TEMPERATURE")
+                .unwrap_or(default.ambient_temperature),
+        };
+        if let Err(e) = config.validate() {
+            panic!("invalid bme680 settings: {}", e);
+        }
+        config
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for os in [
+            self.humidity_oversampling,
+            self.pressure_oversampling,
+            self.temperature_oversampling,
+        ]
+        .iter()
+        {
+            if ![0, 1, 2, 4, 8, 16].contains(os) {
+                return Err(format!("oversampling {} not in 0, 1, 2, 4, 8, 16", os));
+            }
+        }
+        if ![0, 1, 3, 7, 15, 31, 63, 127].contains(&self.iir_filter) {
+            return Err(format!(
+                "iir filter {} not in 0, 1, 3, 7, 15, 31, 63, 127",
+                self.iir_filter
+            ));
+        }
+        if self.heater_temperature > 400 {
+            return Err(format!(
+                "heater temperature {}°C above 400°C",
+                self.heater_temperature
+            ));
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "simulator"))]
+    pub fn settings(&self) -> bme680::Settings {
+        use bme680::*;
+
+        fn oversampling(os: u8) -> OversamplingSetting {
+            match os {
+                1 => OversamplingSetting::OS1x,
+                2 => OversamplingSetting::OS2x,
+                4 => OversamplingSetting::OS4x,
+                8 => OversamplingSetting::OS8x,
+                16 => OversamplingSetting::OS16x,
+                _ => OversamplingSetting::OSNone,
+            }
+        }
+        let filter = match self.iir_filter {
+            1 => IIRFilterSize::Size1,
+            3 => IIRFilterSize::Size3,
+            7 => IIRFilterSize::Size7,
+            15 => IIRFilterSize::Size15,
+            31 => IIRFilterSize::Size31,
+            63 => IIRFilterSize::Size63,
+            127 => IIRFilterSize::Size127,
+            _ => IIRFilterSize::Size0,
+        };
+
+        // temperature offset is part of the Calibration, so the chip reports raw values
+        SettingsBuilder::new()
+            .with_humidity_oversampling(oversampling(self.humidity_oversampling))
+            .with_pressure_oversampling(oversampling(self.pressure_oversampling))
+            .with_temperature_oversampling(oversampling(self.temperature_oversampling))
+            .with_temperature_filter(filter)
+            .with_gas_measurement(
+                self.heater_duration,
+                self.heater_temperature,
+                self.ambient_temperature,
+            )
+            .with_run_gas(true)
+            .build()
+    }
+}
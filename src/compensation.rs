@@ -0,0 +1,157 @@
+use crate::*;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Model for the heat of the raspberry pi reaching the sensor:
+///
+/// `sensor - ambient = factor * (cpu - sensor) + offset`
+///
+/// The display refresh is not a separate term: an e-paper panel only draws
+/// current for the few seconds of a refresh and holds the image without power,
+/// so its share of the heat is small and already part of the fitted `offset`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelfHeating {
+    pub factor: f32,
+    pub offset: f32,
+    pub thermal_zone: PathBuf,
+    /// appends `sensor,cpu,` lines, add the reference temperature and run `aer fit-self-heating <log>`
+    pub log: Option<PathBuf>,
+}
+
+impl SelfHeating {
+    /// Enabled with `SELF_HEATING=<factor>:<offset>` or just `SELF_HEATING_LOG` for collecting data
+    pub fn from_env() -> Option<Self> {
+        let log = dotenv::var("SELF_HEATING_LOG").ok().map(PathBuf::from);
+        let (factor, offset) = match dotenv::var("SELF_HEATING") {
+            Ok(params) => {
+                let mut parts = params.splitn(2, ':').map(|v| v.trim().parse::<f32>());
+                match (parts.next(), parts.next()) {
+                    (Some(Ok(factor)), Some(Ok(offset))) => (factor, offset),
+                    _ => panic!(
+                        "invalid SELF_HEATING '{}', expected <factor>:<offset>",
+                        params
+                    ),
+                }
+            }
+            Err(_) if log.is_some() => (0.0, 0.0),
+            Err(_) => return None,
+        };
+        Some(Self {
+            factor,
+            offset,
+            thermal_zone: dotenv::var("CPU_THERMAL_ZONE")
+                .unwrap_or_else(|_| "/sys/class/thermal/thermal_zone0/temp".into())
+                .into(),
+            log,
+        })
+    }
+
+    pub fn cpu_temperature(&self) -> Result<f32, String> {
+        let millis: i32 = fs::read_to_string(&self.thermal_zone)
+            .map_err(|e| format!("Unable to read {}: {}", self.thermal_zone.display(), e))?
+            .trim()
+            .parse()
+            .map_err(|e| format!("Bad cpu temperature: {}", e))?;
+        Ok(millis as f32 / 1000.0)
+    }
+
+    /// Estimates the ambient temperature and the humidity at that temperature
+    pub fn compensate(&self, reading: Reading) -> Result<Reading, String> {
+        let cpu = self.cpu_temperature()?;
+        if let Some(log) = &self.log {
+            if let Err(e) = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(log)
+                .and_then(|mut f| writeln!(f, "{:.2},{:.2},", reading.temperature, cpu))
+            {
+                error("writing self heating log", e);
+            }
+        }
+
+        let ambient =
+            reading.temperature - (self.factor * (cpu - reading.temperature) + self.offset);
+        Ok(Reading {
+            temperature: ambient,
+            // same amount of water in the air, but colder air holds less of it
+            humidity: reading.humidity.map(|h| {
+                (h * saturation_pressure(reading.temperature) / saturation_pressure(ambient))
+                    .min(100.0)
+            }),
+            ..reading
+        })
+    }
+
+    /// Least squares fit of `factor` and `offset` from `sensor,cpu,reference` lines
+    pub fn fit(log: &str) -> Result<(f32, f32), String> {
+        let mut points = Vec::new();
+        for (i, line) in log.lines().enumerate() {
+            let values: Vec<&str> = line.split(',').map(str::trim).collect();
+            if values.len() < 3 || values[2].is_empty() {
+                continue;
+            }
+            let parse = |v: &str| {
+                v.parse::<f64>()
+                    .map_err(|e| format!("line {}: {}", i + 1, e))
+            };
+            let (sensor, cpu, reference) =
+                (parse(values[0])?, parse(values[1])?, parse(values[2])?);
+            points.push((cpu - sensor, sensor - reference));
+        }
+        if points.len() < 2 {
+            return Err("need at least two lines with a reference temperature".into());
+        }
+
+        let n = points.len() as f64;
+        let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+        let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+        let sxx: f64 = points.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+        let sxy: f64 = points.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+        // the log has two decimals, a smaller spread is rounding noise
+        if sxx / n < 1e-6 {
+            return Err("cpu temperature difference never changes, can't fit a factor".into());
+        }
+        let factor = sxy / sxx;
+        Ok((factor as f32, (mean_y - factor * mean_x) as f32))
+    }
+}
+
+/// Magnus formula, hPa
+fn saturation_pressure(temp: f32) -> f32 {
+    6.112 * (17.62 * temp / (243.12 + temp)).exp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_recovers_factor_and_offset() {
+        let (factor, offset) = (0.12, 0.3);
+        let log: String = [(20.0, 40.0), (21.0, 50.0), (22.5, 55.0), (19.0, 35.0)]
+            .iter()
+            .map(|&(sensor, cpu): &(f32, f32)| {
+                let ambient = sensor - (factor * (cpu - sensor) + offset);
+                format!("{},{},{}\n", sensor, cpu, ambient)
+            })
+            .collect();
+        let (fitted_factor, fitted_offset) = SelfHeating::fit(&log).unwrap();
+        assert!((fitted_factor - factor).abs() < 1e-4, "{}", fitted_factor);
+        assert!((fitted_offset - offset).abs() < 1e-3, "{}", fitted_offset);
+    }
+
+    #[test]
+    fn fit_skips_lines_without_reference() {
+        let log = "20.00,40.00,\n21.00,50.00,17.66\n22.00,52.00,18.44\n";
+        assert!(SelfHeating::fit(log).is_ok());
+        assert!(SelfHeating::fit("20.00,40.00,\n21.00,50.00,17.66\n").is_err());
+        assert!(SelfHeating::fit("20.00,40.00,x\n21.00,50.00,17.66\n").is_err());
+    }
+
+    #[test]
+    fn fit_rejects_constant_input() {
+        let log = "20.1,40.3,18.0\n20.1,40.3,18.1\n20.1,40.3,17.9\n";
+        assert!(SelfHeating::fit(log).is_err());
+    }
+}
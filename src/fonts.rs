@@ -0,0 +1,400 @@
+use crate::*;
+use anyhow::{anyhow, Result};
+use embedded_graphics::{
+    drawable::{Drawable, Pixel},
+    geometry::{Point, Size},
+    primitives::{Circle, Primitive, Rectangle},
+    style::PrimitiveStyle,
+    DrawTarget,
+};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// A glyph of a BDF font, rows are padded to full bytes
+#[derive(Debug, Clone)]
+struct Glyph {
+    /// distance to the next glyph, proportional fonts differ per glyph
+    advance: i32,
+    width: u32,
+    height: u32,
+    /// offset of the bounding box's bottom left corner to the origin on the baseline
+    x_offset: i32,
+    y_offset: i32,
+    rows: Vec<Vec<u8>>,
+}
+
+impl Glyph {
+    fn is_set(&self, x: u32, y: u32) -> bool {
+        self.rows
+            .get(y as usize)
+            .and_then(|row| row.get(x as usize / 8))
+            .map_or(false, |byte| byte & (0x80 >> (x % 8)) != 0)
+    }
+}
+
+/// Bitmap font loaded from a BDF file, e.g. the X11 or terminus fonts,
+/// proportional fonts are supported
+#[derive(Debug, Clone)]
+pub struct BitmapFont {
+    glyphs: HashMap<char, Glyph>,
+    ascent: i32,
+    descent: i32,
+}
+
+impl BitmapFont {
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::parse_bdf(&std::fs::read_to_string(path)?)
+            .map_err(|e| anyhow!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse_bdf(bdf: &str) -> Result<Self> {
+        let mut glyphs = HashMap::new();
+        let mut ascent = None;
+        let mut descent = None;
+        let mut glyph: Option<(Option<char>, Glyph)> = None;
+        let mut in_bitmap = false;
+
+        for (number, line) in bdf.lines().enumerate() {
+            let invalid = || anyhow!("invalid line {}: '{}'", number + 1, line);
+            let mut words = line.split_whitespace();
+            let keyword = words.next().unwrap_or_default();
+            let numbers = |words: std::str::SplitWhitespace| -> Result<Vec<i32>> {
+                words.map(|w| w.parse().map_err(|_| invalid())).collect()
+            };
+
+            if in_bitmap && keyword != "ENDCHAR" {
+                let (_, glyph) = glyph.as_mut().ok_or_else(invalid)?;
+                // slicing below needs single byte chars
+                if keyword.len() % 2 != 0 || !keyword.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    return Err(invalid());
+                }
+                let row = (0..keyword.len() / 2)
+                    .map(|i| u8::from_str_radix(&keyword[2 * i..2 * i + 2], 16))
+                    .collect::<Result<Vec<u8>, _>>()
+                    .map_err(|_| invalid())?;
+                glyph.rows.push(row);
+                continue;
+            }
+            match keyword {
+                "FONT_ASCENT" => ascent = numbers(words)?.first().copied(),
+                "FONT_DESCENT" => descent = numbers(words)?.first().copied(),
+                "STARTCHAR" => {
+                    glyph = Some((
+                        None,
+                        Glyph {
+                            advance: 0,
+                            width: 0,
+                            height: 0,
+                            x_offset: 0,
+                            y_offset: 0,
+                            rows: Vec::new(),
+                        },
+                    ))
+                }
+                "ENCODING" => {
+                    let (c, _) = glyph.as_mut().ok_or_else(invalid)?;
+                    // -1 are glyphs without an encoding
+                    *c = numbers(words)?
+                        .first()
+                        .and_then(|&code| std::char::from_u32(code as u32));
+                }
+                "DWIDTH" => {
+                    let (_, glyph) = glyph.as_mut().ok_or_else(invalid)?;
+                    glyph.advance = *numbers(words)?.first().ok_or_else(invalid)?;
+                }
+                "BBX" => {
+                    let (_, glyph) = glyph.as_mut().ok_or_else(invalid)?;
+                    match numbers(words)?.as_slice() {
+                        &[width, height, x_offset, y_offset] if width >= 0 && height >= 0 => {
+                            glyph.width = width as u32;
+                            glyph.height = height as u32;
+                            glyph.x_offset = x_offset;
+                            glyph.y_offset = y_offset;
+                        }
+                        _ => return Err(invalid()),
+                    }
+                }
+                "BITMAP" => in_bitmap = true,
+                "ENDCHAR" => {
+                    in_bitmap = false;
+                    if let Some((Some(c), glyph)) = glyph.take() {
+                        glyphs.insert(c, glyph);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        match (ascent, descent) {
+            (Some(ascent), Some(descent)) if !glyphs.is_empty() => Ok(Self {
+                glyphs,
+                ascent,
+                descent,
+            }),
+            _ => Err(anyhow!("no FONT_ASCENT, FONT_DESCENT or glyphs")),
+        }
+    }
+
+    pub fn ascent(&self) -> i32 {
+        self.ascent
+    }
+
+    pub fn line_height(&self) -> i32 {
+        self.ascent + self.descent
+    }
+
+    /// Width of the longest line
+    pub fn text_width(&self, text: &str) -> i32 {
+        text.lines()
+            .map(|line| line.chars().map(|c| self.advance(c)).sum())
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn advance(&self, c: char) -> i32 {
+        match self.glyphs.get(&c) {
+            Some(glyph) => glyph.advance,
+            None if c == '°' => self.line_height() / 2,
+            None => self.glyphs.get(&'?').map_or(0, |g| g.advance),
+        }
+    }
+
+    /// Black on white like the embedded-graphics fonts, unknown chars are drawn as `?`
+    pub fn draw<C: Shade, T: DrawTarget<C>>(&self, display: &mut T, text: &str, top_left: Point) {
+        for (i, line) in text.lines().enumerate() {
+            let mut origin = top_left + Point::new(0, i as i32 * self.line_height());
+            for c in line.chars() {
+                let advance = self.advance(c);
+                let _ = Rectangle::new(
+                    origin,
+                    origin + Point::new(advance - 1, self.line_height() - 1),
+                )
+                .into_styled(PrimitiveStyle::with_fill(C::white()))
+                .draw(display);
+
+                if let Some(glyph) = self.glyphs.get(&c) {
+                    self.draw_glyph(display, glyph, origin);
+                } else if c == '°' {
+                    let cell = Size::new(advance as u32, self.ascent as u32);
+                    degree_sign(display, origin, cell);
+                } else if let Some(glyph) = self.glyphs.get(&'?') {
+                    self.draw_glyph(display, glyph, origin);
+                }
+                origin.x += advance;
+            }
+        }
+    }
+
+    fn draw_glyph<C: Shade, T: DrawTarget<C>>(
+        &self,
+        display: &mut T,
+        glyph: &Glyph,
+        origin: Point,
+    ) {
+        let top = origin.y + self.ascent - glyph.y_offset - glyph.height as i32;
+        let left = origin.x + glyph.x_offset;
+        for y in 0..glyph.height {
+            for x in 0..glyph.width {
+                if glyph.is_set(x, y) {
+                    let point = Point::new(left + x as i32, top + y as i32);
+                    let _ = display.draw_pixel(Pixel(point, C::black()));
+                }
+            }
+        }
+    }
+}
+
+/// Ring in the upper half of a character cell, the same at every size
+/// and independent of which latin-1 chars a font has
+pub fn degree_sign<C: Shade, T: DrawTarget<C>>(display: &mut T, top_left: Point, cell: Size) {
+    let radius = (cell.width.min(cell.height) / 5).max(1);
+    let stroke = (cell.height / 16).max(1);
+    let center = top_left + Point::new(cell.width as i32 / 2, (cell.height / 8 + radius) as i32);
+    let _ = Circle::new(center, radius)
+        .into_styled(PrimitiveStyle::with_stroke(C::black(), stroke))
+        .draw(display);
+}
+
+/// Large digits for the clock, drawn from segments so they scale to any size,
+/// the embedded-graphics fonts stop at 24x32
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SevenSegment {
+    /// width of a character cell including the gap to the next one
+    pub width: u32,
+    pub height: u32,
+}
+
+impl SevenSegment {
+    fn stroke(&self) -> i32 {
+        (self.height as i32 / 9).max(2)
+    }
+
+    /// Digits, `:`, `-` and spaces, other chars stay blank
+    pub fn draw<C: Shade, T: DrawTarget<C>>(&self, display: &mut T, text: &str, top_left: Point) {
+        let (w, h) = (self.width as i32, self.height as i32);
+        for (i, c) in text.chars().enumerate() {
+            let origin = top_left + Point::new(i as i32 * w, 0);
+            let _ = Rectangle::new(origin, origin + Point::new(w - 1, h - 1))
+                .into_styled(PrimitiveStyle::with_fill(C::white()))
+                .draw(display);
+            if c == ':' {
+                self.colon(display, origin);
+            } else {
+                for segment in segments(c) {
+                    self.segment(display, origin, *segment);
+                }
+            }
+        }
+    }
+
+    fn colon<C: Shade, T: DrawTarget<C>>(&self, display: &mut T, origin: Point) {
+        let s = self.stroke();
+        let x = self.width as i32 / 2 - s / 2;
+        for y in &[self.height as i32 / 3, self.height as i32 * 2 / 3] {
+            fill(display, origin + Point::new(x, y - s / 2), s, s);
+        }
+    }
+
+    /// Segments a..g, clockwise from the top and the middle one last
+    fn segment<C: Shade, T: DrawTarget<C>>(&self, display: &mut T, origin: Point, segment: u8) {
+        let s = self.stroke();
+        // leave a gap to the next character
+        let w = self.width as i32 - s;
+        let h = self.height as i32;
+        let half = (h - s) / 2;
+        let (x, y, width, height) = match segment {
+            b'a' => (s, 0, w - 2 * s, s),
+            b'b' => (w - s, s, s, half - s),
+            b'c' => (w - s, half + s, s, half - s),
+            b'd' => (s, h - s, w - 2 * s, s),
+            b'e' => (0, half + s, s, half - s),
+            b'f' => (0, s, s, half - s),
+            _ => (s, half, w - 2 * s, s),
+        };
+        fill(display, origin + Point::new(x, y), width, height);
+    }
+}
+
+fn segments(c: char) -> &'static [u8] {
+    match c {
+        '0' => b"abcdef",
+        '1' => b"bc",
+        '2' => b"abged",
+        '3' => b"abgcd",
+        '4' => b"fgbc",
+        '5' => b"afgcd",
+        '6' => b"afgedc",
+        '7' => b"abc",
+        '8' => b"abcdefg",
+        '9' => b"abcdfg",
+        '-' => b"g",
+        _ => b"",
+    }
+}
+
+fn fill<C: Shade, T: DrawTarget<C>>(display: &mut T, top_left: Point, width: i32, height: i32) {
+    if width > 0 && height > 0 {
+        let _ = Rectangle::new(top_left, top_left + Point::new(width - 1, height - 1))
+            .into_styled(PrimitiveStyle::with_fill(C::black()))
+            .draw(display);
+    }
+}
+
+/// Font of the big clock, `CLOCK_FONT=segments|24x32`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClockFont {
+    Segments(SevenSegment),
+    Mono,
+}
+
+impl ClockFont {
+    pub fn from_env() -> Self {
+        match dotenv::var("CLOCK_FONT").as_ref().map(String::as_str) {
+            Ok("segments") | Err(_) => ClockFont::Segments(SevenSegment {
+                width: 24,
+                height: 36,
+            }),
+            Ok("24x32") => ClockFont::Mono,
+            Ok(font) => panic!("invalid CLOCK_FONT '{}', expected segments or 24x32", font),
+        }
+    }
+}
+
+/// Draws the clock with `CLOCK_FONT`, both are 24px wide per char
+pub fn clock_text<C: Shade, T: DrawTarget<C>>(display: &mut T, text: &str, top_left: Point) {
+    match *CLOCK_FONT {
+        ClockFont::Segments(font) => font.draw(display, text, top_left),
+        ClockFont::Mono => text_24x32(display, text, top_left),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_graphics::pixelcolor::BinaryColor;
+
+    const BDF: &str = "STARTFONT 2.1
+FONT_ASCENT 4
+FONT_DESCENT 1
+CHARS 1
+STARTCHAR A
+ENCODING 65
+DWIDTH 6 0
+BBX 5 3 0 0
+BITMAP
+20
+50
+F8
+ENDCHAR
+ENDFONT
+";
+
+    fn is_black(display: &Framebuffer, x: u32, y: u32) -> bool {
+        display.pixel(x, y) == BinaryColor::black()
+    }
+
+    #[test]
+    fn parses_a_glyph() {
+        let font = BitmapFont::parse_bdf(BDF).unwrap();
+        assert_eq!(font.ascent(), 4);
+        assert_eq!(font.line_height(), 5);
+        assert_eq!(font.text_width("AA\nA"), 12);
+
+        let mut display = Framebuffer::new(6, 5);
+        font.draw(&mut display, "A", Point::zero());
+        // the 3 rows sit on the baseline, one below the top
+        assert!(!is_black(&display, 2, 0));
+        assert!(is_black(&display, 2, 1));
+        assert!(is_black(&display, 1, 2) && is_black(&display, 3, 2));
+        assert!((0..5).all(|x| is_black(&display, x, 3)));
+        assert!(!is_black(&display, 5, 3));
+    }
+
+    #[test]
+    fn rejects_malformed_bitmap_rows() {
+        for row in &["5G", "F", "0é0", "\u{e9}"] {
+            let bdf = BDF.replace("\n50\n", &format!("\n{}\n", row));
+            assert!(BitmapFont::parse_bdf(&bdf).is_err(), "{}", row);
+        }
+        assert!(BitmapFont::parse_bdf("FONT_ASCENT 4\nFONT_DESCENT 1\n").is_err());
+    }
+
+    #[test]
+    fn seven_segment_digits() {
+        let font = SevenSegment {
+            width: 24,
+            height: 36,
+        };
+        let mut one = Framebuffer::new(24, 36);
+        font.draw(&mut one, "1", Point::zero());
+        let mut eight = Framebuffer::new(24, 36);
+        font.draw(&mut eight, "8", Point::zero());
+
+        // right segments b and c, the middle one g only for the 8
+        assert!(is_black(&one, 17, 10) && is_black(&one, 17, 26));
+        assert!(!is_black(&one, 10, 17));
+        assert!(is_black(&eight, 10, 17));
+        // the gap to the next character stays blank
+        assert!((0..36).all(|y| !is_black(&eight, 22, y)));
+    }
+}
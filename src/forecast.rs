@@ -1,10 +1,16 @@
 use crate::*;
 use embedded_graphics::transform::Transform;
-use embedded_graphics::{drawable::Drawable, geometry::Point, pixelcolor::BinaryColor, DrawTarget};
+use embedded_graphics::{
+    drawable::Drawable,
+    geometry::Point,
+    primitives::{Line, Primitive},
+    style::PrimitiveStyle,
+    DrawTarget,
+};
 use log::*;
 
 fn tmp_graph_height() -> i32 {
-    120
+    height() * 2 / 5
 }
 
 fn scale(min: i32, max: i32) -> i32 {
@@ -17,9 +23,30 @@ fn scale(min: i32, max: i32) -> i32 {
     }
 }
 
+/// Width of the min/max label of a day, 8 chars of 6x8
+const DAY_LABEL_WIDTH: i32 = 48;
+
+fn slot_width() -> i32 {
+    // 4 days with 8 slots each and some space for the labels
+    (width() - 80) / 32
+}
+
 fn pos_x(day: usize, slot: usize) -> i32 {
-    let mul = 10;
-    (day * 8 + slot) as i32 * mul
+    (day * 8 + slot) as i32 * slot_width()
+}
+
+/// Slots grouped by the calendar days in the timezone with the index of their first slot,
+/// the first and last day are cut off and DST days have 7 or 9 slots
+fn group_days<'a>(slots: &'a [ForecastSlot], timezone: &Tz) -> Vec<(usize, &'a [ForecastSlot])> {
+    let mut days = Vec::new();
+    let mut start = 0;
+    for i in 1..=slots.len() {
+        if i == slots.len() || slots[i].date_in(timezone) != slots[start].date_in(timezone) {
+            days.push((start, &slots[start..i]));
+            start = i;
+        }
+    }
+    days
 }
 
 struct Range {
@@ -51,46 +78,64 @@ impl Range {
     }
 }
 
-pub fn weather_forecast<T: DrawTarget<BinaryColor>>(display: &mut T, current_temp: f32) {
-    let forecast = match openweather::get_5_day_forecast(
-        &WEATHER_LOCATION,
-        &OPENWEATHER_API_KEY,
-        &OPENWEATHER_SETTINGS,
-    ) {
-        Ok(forecast) => forecast,
-        Err(e) => {
-            error("Getting 5 Day Forecast", e);
-            return;
+pub fn weather_forecast<C: Shade, T: DrawTarget<C>>(
+    display: &mut T,
+    provider: &mut dyn WeatherProvider,
+    current_temp: f32,
+) {
+    match provider.forecast() {
+        Ok(slots) => {
+            draw_forecast(display, current_temp, &slots);
+            state().forecast = Some(slots);
         }
-    };
+        Err(e) => error("Getting 5 Day Forecast", e),
+    }
+}
 
+pub fn draw_forecast<C: Shade, T: DrawTarget<C>>(
+    display: &mut T,
+    current_temp: f32,
+    slots: &[ForecastSlot],
+) {
     let mut abs_min = current_temp;
     let mut abs_max: f32 = current_temp;
     let mut temps: Vec<f32> = Vec::new();
+    let mut bands: Vec<(f32, f32)> = Vec::new();
 
-    for (day, day_list) in forecast.list.chunks(8).take(4).enumerate() {
+    // 4 days of 3 hour slots, which can touch 5 calendar days in the `TIMEZONE`
+    let slots = &slots[..slots.len().min(32)];
+
+    for (day, (start, day_list)) in group_days(slots, &TIMEZONE).into_iter().enumerate() {
         let mut min = std::f32::MAX;
         let mut max: f32 = std::f32::MIN;
 
         for h3_slot in day_list.iter() {
             //let tmp = day.main.temp;
-            min = min.min(h3_slot.main.temp_min);
-            max = max.max(h3_slot.main.temp_max);
+            min = min.min(h3_slot.temp_min);
+            max = max.max(h3_slot.temp_max);
             debug!(
                 "Day {}: Norm: {} | Min: {} | Max: {}",
                 day + 1,
-                h3_slot.main.temp,
-                h3_slot.main.temp_min,
-                h3_slot.main.temp_max
+                h3_slot.temp,
+                h3_slot.temp_min,
+                h3_slot.temp_max
             );
-            temps.push(h3_slot.main.temp);
+            temps.push(h3_slot.temp);
+            bands.push((h3_slot.temp_min, h3_slot.temp_max));
         }
         debug!("Day {}: Min: {} | Max: {}", day + 1, min, max);
-        text_6x8(
-            display,
-            &format!("{:6.2}°C\n{:6.2}°C", min, max),
-            (pos_x(day, 4), height() - 20).into(),
-        );
+        // a day with only a few slots left has no room for its label
+        if day_list.len() as i32 * slot_width() >= DAY_LABEL_WIDTH {
+            text_6x8(
+                display,
+                &format!(
+                    "{:>8}\n{:>8}",
+                    UNITS.format_temperature(min, 2),
+                    UNITS.format_temperature(max, 2)
+                ),
+                (pos_x(0, start + day_list.len() / 2), height() - 20).into(),
+            );
+        }
 
         abs_min = abs_min.min(min);
         abs_max = abs_max.max(max);
@@ -107,6 +152,19 @@ pub fn weather_forecast<T: DrawTarget<BinaryColor>>(display: &mut T, current_tem
     )
     .draw(display);
 
+    // min..max of every slot as shaded area behind the line
+    if let Some(gray) = C::light() {
+        let style = PrimitiveStyle::with_stroke(gray, 1);
+        for (counter, (min, max)) in bands.iter().enumerate() {
+            for x in pos_x(0, counter)..pos_x(0, counter + 1) {
+                let _ = Line::new((x, r.pos_y(*min)).into(), (x, r.pos_y(*max)).into())
+                    .into_styled(style)
+                    .translate((basic_x_offset, height()).into())
+                    .draw(display);
+            }
+        }
+    }
+
     let mut prev_temp = current_temp;
     for (counter, temp) in temps.iter().enumerate() {
         let _ = line(
@@ -124,10 +182,10 @@ pub fn weather_forecast<T: DrawTarget<BinaryColor>>(display: &mut T, current_tem
     {
         text_6x8(
             display,
-            &format!("{:3.2}°C", temp),
+            &UNITS.format_temperature(temp as f32, 0),
             (0, height() + r.pos_y(temp as f32)).into(),
         );
-        let _ = line(
+        let _ = grid_line(
             (pos_x(0, 0), r.pos_y(temp as f32)).into(),
             (pos_x(3, 8), r.pos_y(temp as f32)).into(),
         )
@@ -135,3 +193,72 @@ pub fn weather_forecast<T: DrawTarget<BinaryColor>>(display: &mut T, current_tem
         .draw(display);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::{America, Europe};
+
+    /// 32 slots of 3 hours like the OpenWeatherMap forecast
+    fn slots_from(start: DateTime<Utc>) -> Vec<ForecastSlot> {
+        (0..32)
+            .map(|i| ForecastSlot {
+                time: start.timestamp() + i * 3 * 3600,
+                temp: 0.0,
+                temp_min: 0.0,
+                temp_max: 0.0,
+            })
+            .collect()
+    }
+
+    fn day_lengths(slots: &[ForecastSlot], timezone: &Tz) -> Vec<usize> {
+        group_days(slots, timezone)
+            .iter()
+            .map(|(_, day)| day.len())
+            .collect()
+    }
+
+    #[test]
+    fn days_in_utc() {
+        let slots = slots_from(Utc.ymd(2020, 3, 15).and_hms(0, 0, 0));
+        let days = group_days(&slots, &Tz::UTC);
+        assert_eq!(days.len(), 4);
+        assert_eq!(days[1].0, 8);
+        assert_eq!(
+            days[1].1[0].date_in(&Tz::UTC),
+            NaiveDate::from_ymd(2020, 3, 16)
+        );
+    }
+
+    #[test]
+    fn cut_off_days_in_new_york() {
+        // starts at 20:00 of the day before
+        let slots = slots_from(Utc.ymd(2020, 3, 15).and_hms(0, 0, 0));
+        assert_eq!(day_lengths(&slots, &America::New_York), vec![2, 8, 8, 8, 6]);
+    }
+
+    #[test]
+    fn spring_forward_in_berlin() {
+        // 2020-03-29 has 23 hours, the first slot of the day is at 3:00 CEST
+        let slots = slots_from(Utc.ymd(2020, 3, 28).and_hms(1, 0, 0));
+        let days = group_days(&slots, &Europe::Berlin);
+        let (_, sunday) = days[1];
+        assert_eq!(
+            sunday[0].date_in(&Europe::Berlin),
+            NaiveDate::from_ymd(2020, 3, 29)
+        );
+        assert_eq!(sunday.len(), 7);
+    }
+
+    #[test]
+    fn fall_back_in_berlin() {
+        // 2020-10-25 has 25 hours, starting at midnight CEST
+        let slots = slots_from(Utc.ymd(2020, 10, 24).and_hms(22, 0, 0));
+        let days = group_days(&slots, &Europe::Berlin);
+        assert_eq!(days[0].1.len(), 9);
+        assert_eq!(
+            days[1].1[0].date_in(&Europe::Berlin),
+            NaiveDate::from_ymd(2020, 10, 26)
+        );
+    }
+}
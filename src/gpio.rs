@@ -0,0 +1,60 @@
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use gpio_cdev::{errors::Error as CdevError, LineHandle};
+use linux_embedded_hal::{sysfs_gpio, Pin};
+
+/// A pin either via the deprecated sysfs interface, which needs root,
+/// or via the gpio character device, which only needs the `gpio` group
+pub enum AnyPin {
+    Sysfs(Pin),
+    Cdev(LineHandle),
+    /// stands in for a chip select the spi controller drives, changes are ignored
+    Unused,
+}
+
+#[derive(Debug)]
+pub enum PinError {
+    Sysfs(sysfs_gpio::Error),
+    Cdev(CdevError),
+}
+
+impl AnyPin {
+    fn set(&mut self, value: u8) -> Result<(), PinError> {
+        match self {
+            AnyPin::Sysfs(pin) => pin.set_value(value).map_err(PinError::Sysfs),
+            AnyPin::Cdev(line) => line.set_value(value).map_err(PinError::Cdev),
+            AnyPin::Unused => Ok(()),
+        }
+    }
+
+    fn get(&self) -> Result<u8, PinError> {
+        match self {
+            AnyPin::Sysfs(pin) => pin.get_value().map_err(PinError::Sysfs),
+            AnyPin::Cdev(line) => line.get_value().map_err(PinError::Cdev),
+            AnyPin::Unused => Ok(0),
+        }
+    }
+}
+
+impl OutputPin for AnyPin {
+    type Error = PinError;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.set(0)
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.set(1)
+    }
+}
+
+impl InputPin for AnyPin {
+    type Error = PinError;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        self.get().map(|value| value != 0)
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        self.get().map(|value| value == 0)
+    }
+}
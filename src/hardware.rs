@@ -0,0 +1,228 @@
+use crate::*;
+use bme680::{Bme680, I2CAddress, PowerMode};
+use gpio_cdev::{errors::Error as CdevError, Chip, LineRequestFlags};
+use linux_embedded_hal::{
+    i2cdev::linux::LinuxI2CError,
+    spidev::{self, SpidevOptions},
+    sysfs_gpio::{self, Direction},
+    Delay, I2cdev, Pin, Spidev,
+};
+use log::*;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Attempts per init step before giving up
+const ATTEMPTS: u32 = 5;
+/// Wait after the first failed attempt, doubled after every further one
+const BACKOFF: Duration = Duration::from_millis(200);
+/// How long sysfs may take to create the pin directory after the export
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Why a part of the hardware couldn't be set up
+#[derive(Debug)]
+pub enum InitError {
+    Spi {
+        path: String,
+        source: std::io::Error,
+    },
+    Pin {
+        pin: u64,
+        step: &'static str,
+        source: sysfs_gpio::Error,
+    },
+    PinExportTimeout {
+        pin: u64,
+    },
+    Cdev {
+        chip: String,
+        pin: u64,
+        source: CdevError,
+    },
+    Epd(std::io::Error),
+    I2c {
+        path: String,
+        source: LinuxI2CError,
+    },
+    Bme680(String),
+}
+
+impl fmt::Display for InitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InitError::Spi { path, source } => write!(f, "spi {}: {}", path, source),
+            InitError::Pin { pin, step, source } => write!(f, "pin {} {}: {}", pin, step, source),
+            InitError::PinExportTimeout { pin } => {
+                write!(f, "pin {} not exported after {:?}", pin, EXPORT_TIMEOUT)
+            }
+            InitError::Cdev { chip, pin, source } => {
+                write!(f, "pin {} on {}: {}", pin, chip, source)
+            }
+            InitError::Epd(e) => write!(f, "epd: {}", e),
+            InitError::I2c { path, source } => write!(f, "i2c {}: {}", path, source),
+            InitError::Bme680(e) => write!(f, "bme680: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for InitError {}
+
+/// Calls `f` until it succeeds, at most `ATTEMPTS` times with an exponential backoff
+pub fn retry<T, E: fmt::Display>(what: &str, mut f: impl FnMut() -> Result<T, E>) -> Result<T, E> {
+    let mut backoff = BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match f() {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < ATTEMPTS => {
+                warn!(
+                    "{} failed ({}/{}), retrying in {:?}: {}",
+                    what, attempt, ATTEMPTS, backoff, e
+                );
+                thread::sleep(backoff);
+                backoff *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+pub fn init_spi(path: &str, max_speed_hz: u32) -> Result<Spidev, InitError> {
+    let err = |source| InitError::Spi {
+        path: path.to_string(),
+        source,
+    };
+    let mut spi = retry("spi open", || Spidev::open(path)).map_err(err)?;
+    let options = SpidevOptions::new()
+        .bits_per_word(8)
+        .max_speed_hz(max_speed_hz)
+        .mode(spidev::SpiModeFlags::SPI_MODE_0)
+        .build();
+    spi.configure(&options).map_err(err)?;
+    Ok(spi)
+}
+
+/// Sets up the pin with the `GPIO_BACKEND` of the wiring, `value` is only set for outputs
+pub fn open_pin(
+    gpio: &GpioBackend,
+    pin: u64,
+    direction: Direction,
+    value: Option<u8>,
+) -> Result<AnyPin, InitError> {
+    match gpio {
+        GpioBackend::Sysfs => export_pin(pin, direction, value).map(AnyPin::Sysfs),
+        GpioBackend::Cdev { chip } => request_line(chip, pin, direction, value)
+            .map(AnyPin::Cdev)
+            .map_err(|source| InitError::Cdev {
+                chip: chip.clone(),
+                pin,
+                source,
+            }),
+    }
+}
+
+/// Only sysfs pins stay around after exit, cdev lines are released with their handle
+pub fn release_pins(gpio: &GpioBackend, pins: &[u64]) {
+    if *gpio != GpioBackend::Sysfs {
+        return;
+    }
+    for pin in pins {
+        if let Err(e) = Pin::new(*pin).unexport() {
+            error("pin unexport", e);
+        }
+    }
+}
+
+fn request_line(
+    chip: &str,
+    pin: u64,
+    direction: Direction,
+    value: Option<u8>,
+) -> Result<gpio_cdev::LineHandle, CdevError> {
+    let flags = match direction {
+        Direction::In => LineRequestFlags::INPUT,
+        _ => LineRequestFlags::OUTPUT,
+    };
+    Chip::new(chip)?
+        .get_line(pin as u32)?
+        .request(flags, value.unwrap_or(0), "aer")
+}
+
+/// Exports the pin and waits until it is usable
+fn export_pin(pin: u64, direction: Direction, value: Option<u8>) -> Result<Pin, InitError> {
+    let err = |step| move |source| InitError::Pin { pin, step, source };
+    let gpio = Pin::new(pin);
+    retry("pin export", || gpio.export()).map_err(err("export"))?;
+
+    let start = Instant::now();
+    while !gpio.is_exported() {
+        if start.elapsed() > EXPORT_TIMEOUT {
+            return Err(InitError::PinExportTimeout { pin });
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    // udev fixes the permissions of the new files a bit after the export
+    retry("pin direction", || gpio.set_direction(direction)).map_err(err("direction"))?;
+    if let Some(value) = value {
+        retry("pin value", || gpio.set_value(value)).map_err(err("value"))?;
+    }
+    Ok(gpio)
+}
+
+pub fn init_bme680(
+    i2c_device: &str,
+    address: u8,
+    config: &Bme680Config,
+) -> Result<Bme680<I2cdev, Delay>, InitError> {
+    retry("bme680 init", || bme680(i2c_device, address, config))
+}
+
+fn bme680_error<E: fmt::Debug>(e: E) -> InitError {
+    InitError::Bme680(format!("{:?}", e))
+}
+
+fn bme680(
+    i2c_device: &str,
+    address: u8,
+    config: &Bme680Config,
+) -> Result<Bme680<I2cdev, Delay>, InitError> {
+    let address = if address == 0x76 {
+        I2CAddress::Primary
+    } else {
+        I2CAddress::Secondary
+    };
+    let i2c = I2cdev::new(i2c_device).map_err(|source| InitError::I2c {
+        path: i2c_device.to_string(),
+        source,
+    })?;
+    let mut bme = Bme680::init(i2c, Delay {}, address).map_err(bme680_error)?;
+
+    let settings = config.settings();
+
+    let profile_dur = bme.get_profile_dur(&settings.0).map_err(bme680_error)?;
+    info!("Profile duration {:?}", profile_dur);
+    info!("Setting sensor settings");
+    bme.set_sensor_settings(settings).map_err(bme680_error)?;
+    info!("Setting forced power modes");
+    bme.set_sensor_mode(PowerMode::ForcedMode)
+        .map_err(bme680_error)?;
+
+    let sensor_settings = bme.get_sensor_settings(settings.1);
+    info!("Sensor settings: {:?}", sensor_settings);
+
+    let power_mode = bme.get_sensor_mode();
+    info!("Sensor power mode: {:?}", power_mode);
+    info!("Setting forced power modes");
+    bme.set_sensor_mode(PowerMode::ForcedMode)
+        .map_err(bme680_error)?;
+    info!("Retrieving sensor data");
+    let (data, _state) = bme.get_sensor_data().map_err(bme680_error)?;
+    info!("Sensor Data {:?}", data);
+    info!("Temperature {}°C", data.temperature_celsius());
+    info!("Pressure {}hPa", data.pressure_hpa());
+    info!("Humidity {}%", data.humidity_percent());
+    info!("Gas Resistence {}Ω", data.gas_resistance_ohm());
+
+    Ok(bme)
+}
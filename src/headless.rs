@@ -0,0 +1,242 @@
+use crate::*;
+use anyhow::Result;
+use embedded_graphics::{drawable::Pixel, geometry::Size, pixelcolor::BinaryColor, DrawTarget};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Once;
+
+/// In-memory display without any hardware, for screenshots and golden images
+pub struct Framebuffer<C = BinaryColor> {
+    width: u32,
+    height: u32,
+    pixels: Vec<C>,
+}
+
+impl<C: Shade> Framebuffer<C> {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![C::white(); (width * height) as usize],
+        }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> C {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    /// Grays are rounded for the black/white formats
+    fn is_black(&self, x: u32, y: u32) -> bool {
+        self.pixel(x, y).brightness() < 0x80
+    }
+
+    /// Writes a PBM for `.pbm` paths, ANSI art for `.ans` and a PNG otherwise
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let out = BufWriter::new(File::create(path)?);
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("pbm") => self.write_pbm(out),
+            Some("ans") => self.write_ansi(out),
+            _ => self.write_png(out),
+        }
+    }
+
+    pub fn write_pbm<W: Write>(&self, mut out: W) -> Result<()> {
+        write!(out, "P4\n{} {}\n", self.width, self.height)?;
+        let row_bytes = (self.width as usize + 7) / 8;
+        for y in 0..self.height {
+            let mut row = vec![0u8; row_bytes];
+            for x in 0..self.width {
+                if self.is_black(x, y) {
+                    row[x as usize / 8] |= 0x80 >> (x % 8);
+                }
+            }
+            out.write_all(&row)?;
+        }
+        Ok(())
+    }
+
+    /// Two pixels per character with half blocks, black on white
+    pub fn write_ansi<W: Write>(&self, mut out: W) -> Result<()> {
+        for y in (0..self.height).step_by(2) {
+            let mut row = String::from("\x1b[30;107m");
+            for x in 0..self.width {
+                let top = self.is_black(x, y);
+                let bottom = y + 1 < self.height && self.is_black(x, y + 1);
+                row.push(match (top, bottom) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                });
+            }
+            row.push_str("\x1b[0m\n");
+            out.write_all(row.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn write_png<W: Write>(&self, out: W) -> Result<()> {
+        let mut encoder = png::Encoder::new(out, self.width, self.height);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let data: Vec<u8> = self.pixels.iter().map(|p| p.brightness()).collect();
+        encoder.write_header()?.write_image_data(&data)?;
+        Ok(())
+    }
+}
+
+impl<C: Shade> DrawTarget<C> for Framebuffer<C> {
+    type Error = core::convert::Infallible;
+
+    fn draw_pixel(&mut self, Pixel(point, color): Pixel<C>) -> Result<(), Self::Error> {
+        if point.x >= 0
+            && point.y >= 0
+            && (point.x as u32) < self.width
+            && (point.y as u32) < self.height
+        {
+            let index = (point.y as u32 * self.width + point.x as u32) as usize;
+            self.pixels[index] = color;
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+}
+
+/// Every setting that changes how the fixture is drawn, `None` is unset
+const FIXTURE_ENV: [(&str, Option<&str>); 15] = [
+    ("TIMEZONE", Some("UTC")),
+    ("LOCALE", Some("en")),
+    ("TIME_FORMAT", Some("24h")),
+    ("TEMPERATURE_UNIT", Some("c")),
+    ("PRESSURE_UNIT", Some("hpa")),
+    ("CLOCK_FONT", Some("segments")),
+    ("LABEL_FONT", None),
+    ("LATITUDE", Some("48.52")),
+    ("LONGITUDE", Some("9.06")),
+    ("FROST_WARNING", Some("0")),
+    ("IAQ_WARNING", Some("200")),
+    ("LOCATION", Some("golden")),
+    ("SENSOR", Some("golden")),
+    ("SENSORS", None),
+    ("DISPLAY_SENSOR", Some("golden")),
+];
+
+/// Fixed inputs so that renderings only change when the layout does
+pub struct Fixture {
+    pub now: DateTime<Local>,
+    pub weather: Weather,
+    pub forecast: Vec<ForecastSlot>,
+    pub samples: Vec<Sample>,
+}
+
+impl Default for Fixture {
+    fn default() -> Self {
+        let reading = Reading {
+            temperature: 21.4,
+            humidity: Some(43.84),
+            pressure: Some(972.44),
+            gas_resistance: Some(538_138),
+        };
+        let outdoor = Reading {
+            temperature: -3.5,
+            humidity: None,
+            pressure: None,
+            gas_resistance: None,
+        };
+        Self {
+            now: Utc
+                .ymd(2020, 3, 14)
+                .and_hms(9, 26, 53)
+                .with_timezone(&Local),
+            weather: Weather {
+                temperature: -2.3,
+                // 2020-03-14 06:21 and 18:08 UTC
+                sunrise: 1_584_166_860,
+                sunset: 1_584_209_280,
+            },
+            // from 2020-03-15 00:00 UTC, 4 whole days
+            forecast: (0..32)
+                .map(|i| {
+                    let temp = (i as f32 * 0.8).sin() * 6.0 + 2.0;
+                    ForecastSlot {
+                        time: 1_584_230_400 + i as i64 * 3 * 3600,
+                        temp,
+                        temp_min: temp - 1.5,
+                        temp_max: temp + 1.5,
+                    }
+                })
+                .collect(),
+            samples: vec![
+                Sample {
+                    name: DISPLAY_SENSOR.clone(),
+                    latest: reading,
+                    aggregate: Aggregate::of(&[reading]),
+                },
+                Sample {
+                    name: "outdoor".into(),
+                    latest: outdoor,
+                    aggregate: None,
+                },
+            ],
+        }
+    }
+}
+
+impl Fixture {
+    /// Overrides the .env with `FIXTURE_ENV`, has to run before any setting is read
+    pub fn pin_env() {
+        static PINNED: Once = Once::new();
+        PINNED.call_once(|| {
+            // the .env is only loaded once, so it can't bring back the removed ones
+            let _ = dotenv::var("TIMEZONE");
+            for (key, value) in FIXTURE_ENV.iter() {
+                match value {
+                    Some(value) => std::env::set_var(key, value),
+                    None => std::env::remove_var(key),
+                }
+            }
+        });
+    }
+
+    pub fn draw<C: Shade, T: DrawTarget<C>>(&self, display: &mut T) {
+        #[cfg(feature = "layout_large")]
+        draw_forecast(display, self.weather.temperature, &self.forecast);
+        draw_weather(display, &self.weather);
+        draw_time(display, self.now.with_timezone(&*TIMEZONE));
+        draw_astronomy(display, self.now);
+        sensor(display, &self.samples);
+    }
+}
+
+/// Draws to a display and keeps a copy of the frame, e.g. for the http api
+pub struct Mirrored<D> {
+    pub inner: D,
+    pub frame: Framebuffer,
+}
+
+impl<D> Mirrored<D> {
+    pub fn new(inner: D) -> Self {
+        Self {
+            inner,
+            frame: Framebuffer::new(width() as u32, height() as u32),
+        }
+    }
+}
+
+impl<D: DrawTarget<BinaryColor>> DrawTarget<BinaryColor> for Mirrored<D> {
+    type Error = D::Error;
+
+    fn draw_pixel(&mut self, pixel: Pixel<BinaryColor>) -> Result<(), Self::Error> {
+        let _ = self.frame.draw_pixel(pixel);
+        self.inner.draw_pixel(pixel)
+    }
+
+    fn size(&self) -> Size {
+        self.inner.size()
+    }
+}
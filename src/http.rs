@@ -0,0 +1,172 @@
+use crate::*;
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::io::{Cursor, Read};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// Longer messages are cut, they wouldn't fit on the panel anyway
+const MAX_MESSAGE: u64 = 1024;
+
+/// Serves the current state as json and the current frame as png:
+///
+/// - `GET /api/sensors`
+/// - `GET /api/weather`
+/// - `GET /api/forecast`
+/// - `GET /api/status` last refresh and last error
+/// - `GET /frame.png`
+///
+/// and accepts commands with an `Authorization: Bearer <HTTP_TOKEN>` header:
+///
+/// - `POST /api/refresh`
+/// - `POST /api/page/<index>`
+/// - `POST /api/message?secs=<duration>` with the text as body
+/// - `POST /api/reload` restarts with the current .env, if it is valid
+pub fn serve(addr: &str, commands: Sender<Command>) -> Result<()> {
+    let server = Server::http(addr).map_err(|e| anyhow!("Unable to bind {}: {}", addr, e))?;
+    log::info!("Http api listening on {}", addr);
+
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            handle(request, &commands);
+        }
+    });
+    Ok(())
+}
+
+fn handle(mut request: Request, commands: &Sender<Command>) {
+    let response = if *request.method() == Method::Post {
+        match command(&mut request) {
+            Ok(command) => {
+                log::info!("Received {:?}", command);
+                match commands.send(command) {
+                    Ok(()) => Response::from_string("ok"),
+                    Err(_) => Response::from_string("main loop stopped").with_status_code(503),
+                }
+            }
+            Err(response) => response,
+        }
+    } else if *request.method() != Method::Get {
+        Response::from_string("method not allowed").with_status_code(405)
+    } else {
+        let state = state();
+        // e.g. cache busting `/frame.png?t=...`
+        match request.url().splitn(2, '?').next().unwrap_or_default() {
+            "/api/sensors" => json(&state.samples),
+            "/api/weather" => json(&state.weather),
+            "/api/forecast" => json(&state.forecast),
+            "/api/status" => json(&serde_json::json!({
+                "last_refresh": state.last_refresh,
+                "last_error": state.last_error,
+                "page": state.page,
+            })),
+            "/frame.png" if !state.frame.is_empty() => {
+                with_content_type(Response::from_data(state.frame.clone()), "image/png")
+            }
+            _ => Response::from_string("not found").with_status_code(404),
+        }
+    };
+
+    if let Err(e) = request.respond(response) {
+        log::warn!("Unable to respond to http request: {}", e);
+    }
+}
+
+fn command(request: &mut Request) -> Result<Command, Response<Cursor<Vec<u8>>>> {
+    let token = match &*HTTP_TOKEN {
+        Some(token) => format!("Bearer {}", token),
+        None => {
+            return Err(
+                Response::from_string("commands need HTTP_TOKEN to be set").with_status_code(403)
+            )
+        }
+    };
+    if !request.headers().iter().any(|h| {
+        h.field.equiv("Authorization") && constant_time_eq(h.value.as_bytes(), token.as_bytes())
+    }) {
+        return Err(Response::from_string("unauthorized").with_status_code(401));
+    }
+
+    let url = request.url().to_string();
+    let mut parts = url.splitn(2, '?');
+    let path = parts.next().unwrap_or_default();
+    let query = parts.next().unwrap_or_default();
+    let bad_request = |msg: &str| Response::from_string(msg).with_status_code(400);
+
+    match path {
+        "/api/refresh" => Ok(Command::Refresh),
+        "/api/reload" => {
+            check_config().map_err(|e| bad_request(&format!("invalid .env: {}", e)))?;
+            Ok(Command::ReloadConfig)
+        }
+        "/api/message" => {
+            let secs = query
+                .split('&')
+                .find_map(|p| p.strip_prefix("secs="))
+                .map_or(Ok(600), str::parse)
+                .map_err(|_| bad_request("secs must be a number"))?;
+            let mut body = Vec::new();
+            request
+                .as_reader()
+                .take(MAX_MESSAGE)
+                .read_to_end(&mut body)
+                .map_err(|_| bad_request("unable to read the message"))?;
+            let text = match std::str::from_utf8(&body) {
+                Ok(text) => text,
+                // the limit may cut the last char in half
+                Err(e) if e.error_len().is_none() => {
+                    std::str::from_utf8(&body[..e.valid_up_to()]).unwrap_or_default()
+                }
+                Err(_) => return Err(bad_request("message must be utf-8")),
+            };
+            Ok(Command::Message {
+                text: text.trim().to_string(),
+                duration: Duration::from_secs(secs),
+            })
+        }
+        _ => match path.strip_prefix("/api/page/").map(str::parse) {
+            Some(Ok(index)) => Page::from_index(index)
+                .map(Command::Page)
+                .ok_or_else(|| bad_request("no such page")),
+            _ => Err(Response::from_string("not found").with_status_code(404)),
+        },
+    }
+}
+
+/// Takes as long for every token of the same length, so it can't be guessed char by char
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Checks the .env in a new process, the settings of this one are already read
+fn check_config() -> Result<(), String> {
+    let output = relaunch()
+        .map_err(|e| e.to_string())?
+        .arg("--check-config")
+        .output()
+        .map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+fn json<T: Serialize>(value: &T) -> Response<Cursor<Vec<u8>>> {
+    match serde_json::to_vec(value) {
+        Ok(body) => with_content_type(Response::from_data(body), "application/json"),
+        Err(e) => Response::from_string(e.to_string()).with_status_code(500),
+    }
+}
+
+fn with_content_type(
+    response: Response<Cursor<Vec<u8>>>,
+    content_type: &str,
+) -> Response<Cursor<Vec<u8>>> {
+    match Header::from_bytes(&b"Content-Type"[..], content_type.as_bytes()) {
+        Ok(header) => response.with_header(header),
+        Err(_) => response,
+    }
+}
@@ -4,18 +4,77 @@ use influx_db_client::{Point, Precision, Value};
 use tokio;
 
 #[cfg(not(feature = "simulator"))]
-pub(crate) fn sensor_to_influx(temp: f32, humidity: f32, pressure: f32, gas_resistance: u32) {
-    let point = Point::new("sensor")
-        .add_tag("location", LOCATION.clone())
-        .add_tag("sensor", SENSOR.clone())
-        .add_field("temperature", Value::Float(temp as f64))
-        .add_field("humidity", Value::Float(humidity as f64))
-        .add_field("pressure", Value::Float(pressure as f64))
-        .add_field("gasresistence", Value::Integer(gas_resistance as i64));
+pub(crate) fn sensor_to_influx(config: &SensorConfig, reading: &Reading, suspicious: bool) {
+    let reading = METRICS_UNITS.convert(reading);
+    let mut point = Point::new("sensor")
+        .add_tag("location", Value::String(config.location.clone()))
+        .add_tag("sensor", Value::String(config.name.clone()))
+        .add_field("temperature", Value::Float(reading.temperature as f64))
+        .add_field("suspicious", Value::Boolean(suspicious));
+    if let Some(humidity) = reading.humidity {
+        point = point.add_field("humidity", Value::Float(humidity as f64));
+    }
+    if let Some(pressure) = reading.pressure {
+        point = point.add_field("pressure", Value::Float(pressure as f64));
+    }
+    if let Some(gas_resistance) = reading.gas_resistance {
+        point = point.add_field("gasresistence", Value::Integer(gas_resistance as i64));
+    }
+
+    tokio_helper(point)
+}
+
+/// Min/mean/max of a sensor over the `METRICS_WINDOW`, the mean uses the plain field names,
+/// `suspicious` is set like for raw readings if any reading of the window was
+#[cfg(not(feature = "simulator"))]
+pub(crate) fn aggregate_to_influx(config: &SensorConfig, aggregate: &Aggregate, suspicious: usize) {
+    let (min, mean, max) = (
+        METRICS_UNITS.convert(&aggregate.min),
+        METRICS_UNITS.convert(&aggregate.mean),
+        METRICS_UNITS.convert(&aggregate.max),
+    );
+    let mut point = Point::new("sensor")
+        .add_tag("location", Value::String(config.location.clone()))
+        .add_tag("sensor", Value::String(config.name.clone()))
+        .add_field("samples", Value::Integer(aggregate.count as i64))
+        .add_field("suspicious", Value::Boolean(suspicious > 0))
+        .add_field("suspicious_samples", Value::Integer(suspicious as i64));
+    for q in Quantity::ALL.iter() {
+        if let (Some(min), Some(mean), Some(max)) = (q.get(&min), q.get(&mean), q.get(&max)) {
+            point = point
+                .add_field(q.name(), Value::Float(mean as f64))
+                .add_field(format!("{}_min", q.name()), Value::Float(min as f64))
+                .add_field(format!("{}_max", q.name()), Value::Float(max as f64));
+        }
+    }
 
     tokio_helper(point)
 }
 
+/// Number of suspicious values per quantity since startup
+#[cfg(not(feature = "simulator"))]
+pub(crate) fn validation_to_influx(config: &SensorConfig, rejected: &[u64; 4]) {
+    let mut point = Point::new("validation")
+        .add_tag("location", Value::String(config.location.clone()))
+        .add_tag("sensor", Value::String(config.name.clone()));
+    for q in Quantity::ALL.iter() {
+        point = point.add_field(
+            format!("rejected_{}", q.name()),
+            Value::Integer(rejected[*q as usize] as i64),
+        );
+    }
+
+    tokio_helper(point)
+}
+
+// fake sensor data of the simulator isn't stored
+#[cfg(feature = "simulator")]
+pub(crate) fn sensor_to_influx(_: &SensorConfig, _: &Reading, _: bool) {}
+#[cfg(feature = "simulator")]
+pub(crate) fn aggregate_to_influx(_: &SensorConfig, _: &Aggregate, _: usize) {}
+#[cfg(feature = "simulator")]
+pub(crate) fn validation_to_influx(_: &SensorConfig, _: &[u64; 4]) {}
+
 pub fn err_influx(msg: String) {
     let point = Point::new("error")
         .add_tag("location", LOCATION.clone())
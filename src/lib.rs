@@ -1,8 +1,8 @@
 use chrono::prelude::*;
+pub use chrono_tz::Tz;
 use core::fmt::{self, Debug};
-use embedded_graphics::pixelcolor::BinaryColor::{Off as White, On as Black};
 use embedded_graphics::{
-    drawable::Drawable,
+    drawable::{Drawable, Pixel},
     fonts::*,
     geometry::Point,
     pixelcolor::BinaryColor,
@@ -11,101 +11,397 @@ use embedded_graphics::{
     text_style, DrawTarget,
 };
 
+mod shade;
+pub use shade::*;
+mod fonts;
+pub use fonts::*;
+mod locale;
+pub use locale::*;
+mod units;
+pub use units::*;
+mod location;
+pub use location::*;
 mod weather;
 pub use weather::*;
-#[cfg(feature = "epd4in2")]
+mod astronomy;
+pub use astronomy::*;
+#[cfg(feature = "layout_large")]
 mod forecast;
-#[cfg(feature = "epd4in2")]
+#[cfg(feature = "layout_large")]
 pub use forecast::*;
 mod sensor;
 pub use sensor::*;
+#[cfg(not(feature = "simulator"))]
+mod onewire;
+#[cfg(not(feature = "simulator"))]
+pub use onewire::*;
+#[cfg(not(feature = "simulator"))]
+mod wiring;
+#[cfg(not(feature = "simulator"))]
+pub use wiring::*;
+#[cfg(not(feature = "simulator"))]
+mod gpio;
+#[cfg(not(feature = "simulator"))]
+pub use gpio::*;
+#[cfg(not(feature = "simulator"))]
+mod hardware;
+#[cfg(not(feature = "simulator"))]
+pub use hardware::*;
+mod compensation;
+pub use compensation::*;
+mod calibration;
+pub use calibration::*;
+mod sampler;
+pub use sampler::*;
+mod validation;
+pub use validation::*;
 mod time;
 pub use time::*;
 mod influx;
 pub use influx::*;
+mod scenario;
+pub use scenario::*;
+mod tiles;
+pub use tiles::*;
+mod warning;
+pub use warning::*;
+mod mqtt;
+pub use mqtt::*;
+mod screen;
+pub use screen::*;
+mod http;
+pub use http::*;
+mod state;
+pub use state::*;
+mod headless;
+pub use headless::*;
+mod output;
+pub use output::*;
 mod static_vars;
 pub use static_vars::*;
 
+// portrait panels are rotated by 90°
 pub fn height() -> i32 {
+    #[cfg(feature = "epd1in54")]
+    return epd_waveshare::epd1in54::HEIGHT as i32;
+    #[cfg(feature = "epd2in13")]
+    return epd_waveshare::epd2in13_v2::WIDTH as i32;
     #[cfg(feature = "epd2in9")]
     return epd_waveshare::epd2in9::WIDTH as i32;
+    #[cfg(feature = "epd2in9bc")]
+    return epd_waveshare::epd2in9bc::WIDTH as i32;
     #[cfg(feature = "epd4in2")]
     return epd_waveshare::epd4in2::HEIGHT as i32;
+    #[cfg(feature = "epd7in5")]
+    return epd_waveshare::epd7in5_v2::HEIGHT as i32;
 }
 
 pub fn width() -> i32 {
+    #[cfg(feature = "epd1in54")]
+    return epd_waveshare::epd1in54::WIDTH as i32;
+    #[cfg(feature = "epd2in13")]
+    return epd_waveshare::epd2in13_v2::HEIGHT as i32;
     #[cfg(feature = "epd2in9")]
     return epd_waveshare::epd2in9::HEIGHT as i32;
+    #[cfg(feature = "epd2in9bc")]
+    return epd_waveshare::epd2in9bc::HEIGHT as i32;
     #[cfg(feature = "epd4in2")]
     return epd_waveshare::epd4in2::WIDTH as i32;
+    #[cfg(feature = "epd7in5")]
+    return epd_waveshare::epd7in5_v2::WIDTH as i32;
 }
 
-pub fn style_def() -> PrimitiveStyle<BinaryColor> {
-    PrimitiveStyle::with_stroke(Black, 1)
+/// Feature name of the panel, e.g. for the golden images
+pub fn panel() -> &'static str {
+    #[cfg(feature = "epd1in54")]
+    return "epd1in54";
+    #[cfg(feature = "epd2in13")]
+    return "epd2in13";
+    #[cfg(feature = "epd2in9")]
+    return "epd2in9";
+    #[cfg(feature = "epd2in9bc")]
+    return "epd2in9bc";
+    #[cfg(feature = "epd4in2")]
+    return "epd4in2";
+    #[cfg(feature = "epd7in5")]
+    return "epd7in5";
+}
+
+/// Narrow panels don't fit two big temperatures next to each other
+pub fn narrow() -> bool {
+    width() < 296
 }
 
-pub fn line(start: Point, end: Point) -> Styled<Line, PrimitiveStyle<BinaryColor>> {
+pub fn style_def<C: Shade>() -> PrimitiveStyle<C> {
+    PrimitiveStyle::with_stroke(C::black(), 1)
+}
+
+pub fn line<C: Shade>(start: Point, end: Point) -> Styled<Line, PrimitiveStyle<C>> {
     Line::new(start, end).into_styled(style_def())
 }
 
-pub fn rectangle(start: Point, end: Point) -> Styled<Rectangle, PrimitiveStyle<BinaryColor>> {
-    Rectangle::new(start, end).into_styled(PrimitiveStyle::with_fill(White))
+/// Light gray with grayscale, black otherwise
+pub fn grid_line<C: Shade>(start: Point, end: Point) -> Styled<Line, PrimitiveStyle<C>> {
+    let color = C::light().unwrap_or_else(C::black);
+    Line::new(start, end).into_styled(PrimitiveStyle::with_stroke(color, 1))
 }
 
-pub fn text_6x8<T: DrawTarget<BinaryColor>>(display: &mut T, text: &str, top_left: Point) {
+pub fn rectangle<C: Shade>(start: Point, end: Point) -> Styled<Rectangle, PrimitiveStyle<C>> {
+    Rectangle::new(start, end).into_styled(PrimitiveStyle::with_fill(C::white()))
+}
+
+pub fn text_6x8<C: Shade, T: DrawTarget<C>>(display: &mut T, text: &str, top_left: Point) {
     draw_text(display, text, top_left, Font6x8);
 }
 
 /// Doesn't support as many different ascii chars
-pub fn text_6x12<T: DrawTarget<BinaryColor>>(display: &mut T, text: &str, top_left: Point) {
+pub fn text_6x12<C: Shade, T: DrawTarget<C>>(display: &mut T, text: &str, top_left: Point) {
     draw_text(display, text, top_left, Font6x12);
 }
 
-pub fn text_8x16<T: DrawTarget<BinaryColor>>(display: &mut T, text: &str, top_left: Point) {
-    draw_text(display, text, top_left, Font8x16);
+/// Uses the `LABEL_FONT` instead if there is one
+pub fn text_8x16<C: Shade, T: DrawTarget<C>>(display: &mut T, text: &str, top_left: Point) {
+    match &*LABEL_FONT {
+        Some(font) => font.draw(display, text, top_left),
+        None => draw_text(display, text, top_left, Font8x16),
+    }
 }
 
-pub fn text_12x16<T: DrawTarget<BinaryColor>>(display: &mut T, text: &str, top_left: Point) {
+pub fn text_12x16<C: Shade, T: DrawTarget<C>>(display: &mut T, text: &str, top_left: Point) {
     draw_text(display, text, top_left, Font12x16);
 }
-pub fn text_24x32<T: DrawTarget<BinaryColor>>(display: &mut T, text: &str, top_left: Point) {
+pub fn text_24x32<C: Shade, T: DrawTarget<C>>(display: &mut T, text: &str, top_left: Point) {
     draw_text(display, text, top_left, Font24x32);
 }
 
-pub fn draw_text<T: DrawTarget<BinaryColor>, F: Copy + Font>(
+pub fn draw_text<C: Shade, T: DrawTarget<C>, F: Copy + Font>(
     display: &mut T,
     text: &str,
     top_left: Point,
     font: F,
 ) {
+    if let Some(gray) = C::light() {
+        if F::CHARACTER_SIZE.height >= 32 {
+            return draw_text_smooth(display, text, top_left, font, gray);
+        }
+    }
+    // the fonts differ in which latin-1 chars they have, so ° is always drawn by hand
+    let plain = text.replace('°', " ");
     // epd4in2 doesn't fail there
-    let _ = Text::new(text, top_left)
+    let _ = Text::new(&plain, top_left)
         .into_styled(text_style!(
             font = font,
-            text_color = Black,
-            background_color = White
+            text_color = C::black(),
+            background_color = C::white()
         ))
         .draw(display);
+
+    let cell = F::CHARACTER_SIZE;
+    for (row, line) in text.lines().enumerate() {
+        for (column, c) in line.chars().enumerate() {
+            if c == '°' {
+                let offset = Point::new(
+                    column as i32 * cell.width as i32,
+                    row as i32 * cell.height as i32,
+                );
+                degree_sign(display, top_left + offset, cell);
+            }
+        }
+    }
 }
 
-fn daystr(day: &Weekday) -> &str {
-    match day {
-        Weekday::Mon => "Mon",
-        Weekday::Tue => "Tue",
-        Weekday::Wed => "Wed",
-        Weekday::Thu => "Thu",
-        Weekday::Fri => "Fri",
-        Weekday::Sat => "Sat",
-        Weekday::Sun => "Sun",
+/// Fills the steps of diagonal strokes with gray, the large fonts look jagged otherwise
+fn draw_text_smooth<C: Shade, T: DrawTarget<C>, F: Copy + Font>(
+    display: &mut T,
+    text: &str,
+    top_left: Point,
+    font: F,
+    gray: C,
+) {
+    let columns = text.lines().map(|l| l.chars().count()).max().unwrap_or(0) as u32;
+    let rows = text.lines().count() as u32;
+    let mut glyphs: Framebuffer<BinaryColor> = Framebuffer::new(
+        columns * F::CHARACTER_SIZE.width,
+        rows * F::CHARACTER_SIZE.height,
+    );
+    draw_text(&mut glyphs, text, Point::zero(), font);
+
+    let size = glyphs.size();
+    let black = |x: i32, y: i32| {
+        x >= 0
+            && y >= 0
+            && (x as u32) < size.width
+            && (y as u32) < size.height
+            && glyphs.pixel(x as u32, y as u32) == BinaryColor::On
+    };
+    for y in 0..size.height as i32 {
+        for x in 0..size.width as i32 {
+            let color = if black(x, y) {
+                C::black()
+            } else if (black(x - 1, y) || black(x + 1, y)) && (black(x, y - 1) || black(x, y + 1)) {
+                gray
+            } else {
+                C::white()
+            };
+            let _ = display.draw_pixel(Pixel(top_left + Point::new(x, y), color));
+        }
+    }
+}
+
+/// The text helpers above as a value, to measure and align text
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextFont {
+    F6x8,
+    F6x12,
+    /// or the `LABEL_FONT`
+    F8x16,
+    F12x16,
+    F24x32,
+    /// the `CLOCK_FONT`
+    Clock,
+}
+
+impl TextFont {
+    pub fn line_height(self) -> i32 {
+        match self {
+            TextFont::F6x8 => 8,
+            TextFont::F6x12 => 12,
+            TextFont::F8x16 => LABEL_FONT.as_ref().map_or(16, BitmapFont::line_height),
+            TextFont::F12x16 => 16,
+            TextFont::F24x32 => 32,
+            TextFont::Clock => match *CLOCK_FONT {
+                ClockFont::Segments(font) => font.height as i32,
+                ClockFont::Mono => 32,
+            },
+        }
+    }
+
+    /// Distance from the top to the baseline
+    pub fn baseline(self) -> i32 {
+        match (self, &*LABEL_FONT) {
+            (TextFont::F8x16, Some(font)) => font.ascent(),
+            (TextFont::Clock, _) => self.line_height(),
+            // the embedded-graphics fonts leave about an eighth for descenders
+            _ => self.line_height() * 7 / 8,
+        }
+    }
+
+    /// Width of the longest line
+    pub fn text_width(self, text: &str) -> i32 {
+        let char_width = match (self, &*LABEL_FONT) {
+            (TextFont::F8x16, Some(font)) => return font.text_width(text),
+            (TextFont::F6x8, _) | (TextFont::F6x12, _) => 6,
+            (TextFont::F8x16, None) => 8,
+            (TextFont::F12x16, _) => 12,
+            (TextFont::F24x32, _) | (TextFont::Clock, _) => 24,
+        };
+        let chars = text.lines().map(|l| l.chars().count()).max().unwrap_or(0);
+        chars as i32 * char_width
+    }
+
+    pub fn draw<C: Shade, T: DrawTarget<C>>(self, display: &mut T, text: &str, top_left: Point) {
+        match self {
+            TextFont::F6x8 => text_6x8(display, text, top_left),
+            TextFont::F6x12 => text_6x12(display, text, top_left),
+            TextFont::F8x16 => text_8x16(display, text, top_left),
+            TextFont::F12x16 => text_12x16(display, text, top_left),
+            TextFont::F24x32 => text_24x32(display, text, top_left),
+            TextFont::Clock => clock_text(display, text, top_left),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HAlign {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VAlign {
+    Top,
+    Center,
+    Bottom,
+    /// the baseline of the last line on the bottom edge, descenders may reach below
+    Baseline,
+}
+
+/// Bounds for `text_aligned` from the top left corner and the size
+pub fn area(top_left: Point, width: i32, height: i32) -> Rectangle {
+    Rectangle::new(top_left, top_left + Point::new(width - 1, height - 1))
+}
+
+/// Shortens a line with `..` until it fits into `max_width`
+pub fn truncate(font: TextFont, text: &str, max_width: i32) -> String {
+    if font.text_width(text) <= max_width {
+        return text.to_string();
+    }
+    let mut chars: Vec<char> = text.chars().collect();
+    while !chars.is_empty() {
+        chars.pop();
+        let shortened = chars.iter().collect::<String>() + "..";
+        if font.text_width(&shortened) <= max_width {
+            return shortened;
+        }
+    }
+    String::new()
+}
+
+/// Clears `bounds` (corners inclusive) and draws the text aligned within,
+/// lines that are too long are truncated
+pub fn text_aligned<C: Shade, T: DrawTarget<C>>(
+    display: &mut T,
+    font: TextFont,
+    text: &str,
+    bounds: Rectangle,
+    h: HAlign,
+    v: VAlign,
+) {
+    let _ = rectangle(bounds.top_left, bounds.bottom_right).draw(display);
+    let width = bounds.bottom_right.x - bounds.top_left.x + 1;
+    let height = bounds.bottom_right.y - bounds.top_left.y + 1;
+    let lines = text.lines().count() as i32;
+    let block = lines * font.line_height();
+    let top = bounds.top_left.y
+        + match v {
+            VAlign::Top => 0,
+            VAlign::Center => (height - block) / 2,
+            VAlign::Bottom => height - block,
+            VAlign::Baseline => height - (lines - 1) * font.line_height() - font.baseline(),
+        };
+
+    for (i, line) in text.lines().enumerate() {
+        let line = truncate(font, line, width);
+        let left = bounds.top_left.x
+            + match h {
+                HAlign::Left => 0,
+                HAlign::Center => (width - font.text_width(&line)) / 2,
+                HAlign::Right => width - font.text_width(&line),
+            };
+        let y = top + i as i32 * font.line_height();
+        font.draw(display, &line, Point::new(left, y));
     }
 }
 
 pub fn error<T: core::fmt::Display>(desc: &str, error: T) {
     let fmt = format!("Error in {}: {}", desc, error);
     log::error!("{}", &fmt);
+    state().last_error = Some(ErrorState {
+        time: Local::now(),
+        message: fmt.clone(),
+    });
     err_influx(fmt);
 }
 
+/// This program with the environment it was started with, without the variables
+/// from the .env, so the new process reads the current .env
+pub fn relaunch() -> std::io::Result<std::process::Command> {
+    let mut command = std::process::Command::new(std::env::current_exe()?);
+    command.env_clear().envs(LAUNCH_ENV.iter().cloned());
+    Ok(command)
+}
+
 #[derive(Debug)]
 pub enum Status {
     STARTUP,
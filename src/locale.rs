@@ -0,0 +1,123 @@
+use chrono::prelude::*;
+use openweather::Language;
+
+/// Language of the drawn texts and the weather api, `LOCALE=en|de`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Locale {
+    English,
+    German,
+}
+
+impl Locale {
+    pub fn from_env() -> Self {
+        match dotenv::var("LOCALE").as_ref().map(String::as_str) {
+            Ok("en") | Err(_) => Locale::English,
+            Ok("de") => Locale::German,
+            Ok(locale) => panic!("invalid LOCALE '{}', expected en or de", locale),
+        }
+    }
+
+    /// For the descriptions OpenWeatherMap returns
+    pub fn language(self) -> Language {
+        match self {
+            Locale::English => Language::English,
+            Locale::German => Language::German,
+        }
+    }
+
+    /// Abbreviated to three chars
+    pub fn weekday(self, day: Weekday) -> &'static str {
+        let days = match self {
+            Locale::English => ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+            Locale::German => ["Mo.", "Di.", "Mi.", "Do.", "Fr.", "Sa.", "So."],
+        };
+        days[day.num_days_from_monday() as usize]
+    }
+
+    /// Abbreviated to three chars, `month` starts at 1
+    pub fn month(self, month: u32) -> &'static str {
+        let months = match self {
+            Locale::English => [
+                "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+            ],
+            Locale::German => [
+                "Jan", "Feb", "Mär", "Apr", "Mai", "Jun", "Jul", "Aug", "Sep", "Okt", "Nov", "Dez",
+            ],
+        };
+        months[(month as usize + 11) % 12]
+    }
+
+    /// 10 chars, e.g. `2020/05/31` or `31.05.2020`
+    pub fn date<Tz: TimeZone>(self, date: &DateTime<Tz>) -> String {
+        match self {
+            Locale::English => format!("{:4}/{:02}/{:02}", date.year(), date.month(), date.day()),
+            Locale::German => format!("{:02}.{:02}.{:4}", date.day(), date.month(), date.year()),
+        }
+    }
+
+    /// 5 chars without the year, e.g. `05/31` or `31.05`
+    pub fn short_date<Tz: TimeZone>(self, date: &DateTime<Tz>) -> String {
+        match self {
+            Locale::English => format!("{:02}/{:02}", date.month(), date.day()),
+            Locale::German => format!("{:02}.{:02}", date.day(), date.month()),
+        }
+    }
+
+    /// e.g. `May 31` or `31. Mai`
+    pub fn day_and_month<Tz: TimeZone>(self, date: &DateTime<Tz>) -> String {
+        match self {
+            Locale::English => format!("{} {}", self.month(date.month()), date.day()),
+            Locale::German => format!("{}. {}", date.day(), self.month(date.month())),
+        }
+    }
+
+    pub fn decimal_separator(self) -> char {
+        match self {
+            Locale::English => '.',
+            Locale::German => ',',
+        }
+    }
+
+    /// Fixed number of decimals with the separator of the locale
+    pub fn number(self, value: impl Into<f64>, decimals: usize) -> String {
+        let number = format!("{:.*}", decimals, value.into());
+        match self.decimal_separator() {
+            '.' => number,
+            separator => number.replace('.', &separator.to_string()),
+        }
+    }
+
+    /// Shown while the panel sleeps after a shutdown
+    pub fn offline_since(self) -> (&'static str, &'static str) {
+        match self {
+            Locale::English => ("offline", "since"),
+            Locale::German => ("offline", "seit"),
+        }
+    }
+}
+
+/// `TIME_FORMAT=24h|12h`, the clock has no room for am/pm
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeFormat {
+    H24,
+    H12,
+}
+
+impl TimeFormat {
+    pub fn from_env() -> Self {
+        match dotenv::var("TIME_FORMAT").as_ref().map(String::as_str) {
+            Ok("24h") | Err(_) => TimeFormat::H24,
+            Ok("12h") => TimeFormat::H12,
+            Ok(format) => panic!("invalid TIME_FORMAT '{}', expected 24h or 12h", format),
+        }
+    }
+
+    /// e.g. `7:05` or `19:05`, without padding
+    pub fn time<T: Timelike>(self, time: &T) -> String {
+        let hour = match self {
+            TimeFormat::H24 => time.hour(),
+            TimeFormat::H12 => time.hour12().1,
+        };
+        format!("{}:{:02}", hour, time.minute())
+    }
+}
@@ -0,0 +1,61 @@
+use openweather::LocationSpecifier;
+use serde::Serialize;
+
+/// `LATITUDE` and `LONGITUDE` in degrees, north and east are positive
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Coordinates {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl Coordinates {
+    pub fn from_env() -> Option<Self> {
+        let var = |key: &str, max: f64| {
+            dotenv::var(key)
+                .ok()
+                .map(|value| match value.parse::<f64>() {
+                    Ok(degrees) if degrees.abs() <= max => degrees,
+                    _ => panic!(
+                        "invalid {} '{}', expected -{max}..{max}",
+                        key,
+                        value,
+                        max = max
+                    ),
+                })
+        };
+        match (var("LATITUDE", 90.0), var("LONGITUDE", 180.0)) {
+            (Some(latitude), Some(longitude)) => Some(Self {
+                latitude,
+                longitude,
+            }),
+            (None, None) => None,
+            _ => panic!("LATITUDE and LONGITUDE are only valid together"),
+        }
+    }
+}
+
+/// The first of `CITY_ID`, `ZIP` (with `COUNTRY`), `CITY` (with `COUNTRY`)
+/// and the `Coordinates` that is configured
+pub fn weather_location_from_env() -> LocationSpecifier {
+    let country = || dotenv::var("COUNTRY").expect("get COUNTRY (e.g. 'DE') key from .env file");
+    if let Ok(id) = dotenv::var("CITY_ID") {
+        LocationSpecifier::CityId(id)
+    } else if let Ok(zip) = dotenv::var("ZIP") {
+        LocationSpecifier::ZipCode {
+            zip,
+            country: country(),
+        }
+    } else if let Ok(city) = dotenv::var("CITY") {
+        LocationSpecifier::CityAndCountryName {
+            city,
+            country: country(),
+        }
+    } else if let Some(coordinates) = Coordinates::from_env() {
+        LocationSpecifier::Coordinates {
+            lat: coordinates.latitude as f32,
+            lon: coordinates.longitude as f32,
+        }
+    } else {
+        panic!("get CITY_ID, ZIP, CITY (e.g. 'Tübingen') or LATITUDE/LONGITUDE from .env file")
+    }
+}
@@ -1,136 +1,460 @@
 // activate spi, gpio in raspi-config
-// needs to be run with sudo because of some sysfs_gpio permission problems and follow-up timing problems
+// needs to be run with sudo because of some sysfs_gpio permission problems and follow-up timing problems,
+// with GPIO_BACKEND=cdev being in the gpio group is enough
 // see https://github.com/rust-embedded/rust-sysfs-gpio/issues/5 and follow-up issues
 
 use aer::*;
 use anyhow::Result;
 use core::time::Duration;
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::thread;
 
+#[cfg(all(feature = "epd1in54", not(feature = "simulator")))]
+use epd_waveshare::epd1in54::{Display1in54 as DisplayEPD, Epd1in54 as EPD};
+#[cfg(all(feature = "epd2in13", not(feature = "simulator")))]
+use epd_waveshare::epd2in13_v2::{Display2in13 as DisplayEPD, Epd2in13 as EPD};
+#[cfg(all(feature = "epd2in9", not(feature = "simulator")))]
+use epd_waveshare::epd2in9::{Display2in9 as DisplayEPD, Epd2in9 as EPD};
+#[cfg(all(feature = "epd2in9bc", not(feature = "simulator")))]
+use epd_waveshare::epd2in9bc::{Display2in9bc as DisplayEPD, Epd2in9bc as EPD};
+#[cfg(all(feature = "epd4in2", not(feature = "simulator")))]
+use epd_waveshare::epd4in2::{Display4in2 as DisplayEPD, Epd4in2 as EPD};
+#[cfg(all(feature = "epd7in5", not(feature = "simulator")))]
+use epd_waveshare::epd7in5_v2::{Display7in5 as DisplayEPD, Epd7in5 as EPD};
+
 fn main() {
+    // before anything reads the .env
+    let _ = &*LAUNCH_ENV;
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("fit-self-heating") {
+        return fit_self_heating(args.get(2));
+    }
+    // used by `POST /api/reload` before the restart, invalid settings also panic
+    if args.get(1).map(String::as_str) == Some("--check-config") {
+        if let Err(e) = check_config() {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(i) = args.iter().position(|a| a == "--render-once") {
+        let fixture = args.iter().any(|a| a == "--fixture");
+        let scenario = args
+            .iter()
+            .position(|a| a == "--scenario")
+            .and_then(|i| args.get(i + 1));
+        let gray = args.iter().any(|a| a == "--gray");
+        let rendered = if gray {
+            render_once::<embedded_graphics::pixelcolor::Gray2>(args.get(i + 1), fixture, scenario)
+        } else {
+            render_once::<embedded_graphics::pixelcolor::BinaryColor>(
+                args.get(i + 1),
+                fixture,
+                scenario,
+            )
+        };
+        if let Err(e) = rendered {
+            eprintln!("Rendering failed: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     if let Err(e) = run() {
         eprintln!("Program exited early with error: {}", e);
     }
 }
 
-#[cfg(not(feature = "simulator"))]
-fn run() -> Result<()> {
-    use bme680::*;
-    #[cfg(feature = "epd2in9")]
-    use epd_waveshare::epd2in9::{Display2in9 as DisplayEPD, EPD2in9 as EPD};
-    #[cfg(feature = "epd4in2")]
-    use epd_waveshare::epd4in2::{Display4in2 as DisplayEPD, EPD4in2 as EPD};
-    use epd_waveshare::prelude::*;
-    use linux_embedded_hal::*;
-    use linux_embedded_hal::{
-        spidev::{self, SpidevOptions},
-        sysfs_gpio::Direction,
-        Delay, Pin, Spidev,
+/// Why the main loop ended
+enum Exit {
+    /// the reason is reported with the SHUTDOWN status
+    Shutdown(String),
+    /// restart with the new .env, it was checked by the http api
+    Reload,
+}
+
+/// Sleeps until the next refresh is due or a command asks for it earlier,
+/// returns why if the main loop has to end
+fn wait_for_command(
+    commands: &Receiver<Command>,
+    screen: &mut Screen,
+    timeout: Duration,
+) -> Option<Exit> {
+    let command = match commands.recv_timeout(timeout) {
+        Ok(command) => command,
+        Err(_) => return None,
     };
-    use log::*;
+    match command {
+        Command::ReloadConfig => return Some(Exit::Reload),
+        Command::Shutdown(reason) => return Some(Exit::Shutdown(reason)),
+        command => screen.apply(command),
+    }
+    None
+}
 
-    env_logger::init();
+/// Turns SIGTERM and SIGINT into `Command::Shutdown`, so the current refresh can finish
+fn forward_signals(commands: Sender<Command>) -> Result<()> {
+    use signal_hook::{iterator::Signals, SIGINT, SIGTERM};
+
+    let signals = Signals::new(&[SIGTERM, SIGINT])?;
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            let reason = match signal {
+                SIGTERM => "SIGTERM",
+                _ => "SIGINT",
+            };
+            if commands.send(Command::Shutdown(reason.into())).is_err() {
+                return;
+            }
+        }
+    });
+    Ok(())
+}
 
-    status_influx(Status::STARTUP, None);
+/// Reports the shutdown after everything pending went out
+fn shutdown(sampler: Sampler, reason: String) {
+    sampler.stop();
+    status_influx(Status::SHUTDOWN, Some(reason));
+    status_mqtt(&Status::SHUTDOWN);
+    disconnect_mqtt();
+}
 
-    // Configure SPI
-    // Settings are taken from
-    let mut spi = Spidev::open("/dev/spidev0.0").expect("spidev directory");
-    let options = SpidevOptions::new()
-        .bits_per_word(8)
-        .max_speed_hz(4_000_000)
-        .mode(spidev::SpiModeFlags::SPI_MODE_0)
-        .build();
-    spi.configure(&options).expect("spi configuration");
+/// Restarts the process in place, so the .env is read again,
+/// pending metrics are sent first and it only returns if the restart failed
+fn reload(sampler: Sampler) -> std::io::Error {
+    use std::os::unix::process::CommandExt;
 
-    // Configure Digital I/O Pin to be used as Chip Select for SPI
-    let cs = Pin::new(26); //BCM7 CE0
-    cs.export().expect("cs export");
-    while !cs.is_exported() {}
-    cs.set_direction(Direction::Out).expect("CS Direction");
-    cs.set_value(1).expect("CS Value set to 1");
+    sampler.stop();
+    match relaunch() {
+        Ok(mut command) => command.args(std::env::args().skip(1)).exec(),
+        Err(e) => e,
+    }
+}
 
-    let busy = Pin::new(5); //pin 29
-    busy.export().expect("busy export");
-    while !busy.is_exported() {}
-    busy.set_direction(Direction::In).expect("busy Direction");
-    //busy.set_value(1).expect("busy Value set to 1");
+/// Reads every setting, so invalid ones panic right away, and rejects
+/// configurations which would otherwise fail in the middle of a refresh
+fn check_config() -> Result<()> {
+    let _ = (&*COORDINATES, &*LOCALE, &*TIME_FORMAT, &*TIMEZONE, &*UNITS);
+    let _ = (
+        &*METRICS_UNITS,
+        &*LOCATION,
+        &*DISPLAY,
+        &*SENSORS,
+        &*TILES,
+        &*PAGES,
+    );
+    let _ = (
+        &*OUTPUT,
+        &*CLOCK_FONT,
+        &*LABEL_FONT,
+        &*FROST_WARNING,
+        &*IAQ_WARNING,
+    );
+    let _ = (
+        &*SAMPLE_INTERVAL,
+        &*METRICS_WINDOW,
+        &*SELF_HEATING,
+        &*VALIDATION,
+    );
+    // the simulator stores nothing and may replay the weather of a scenario
+    #[cfg(not(feature = "simulator"))]
+    let _ = (&*WEATHER_LOCATION, &*OPENWEATHER_API_KEY, &*INFLUX_CLIENT);
+    #[cfg(not(feature = "simulator"))]
+    Wiring::from_env()?;
+    SensorConfig::display_sensor(&SENSORS).map_err(anyhow::Error::msg)?;
+    Ok(())
+}
 
-    let dc = Pin::new(6); //pin 31 //bcm6
-    dc.export().expect("dc export");
-    while !dc.is_exported() {}
-    dc.set_direction(Direction::Out).expect("dc Direction");
-    dc.set_value(1).expect("dc Value set to 1");
+/// Prints `SELF_HEATING` parameters for a log collected with `SELF_HEATING_LOG`
+fn fit_self_heating(path: Option<&String>) {
+    let path = match path {
+        Some(path) => path,
+        None => {
+            return eprintln!("Usage: aer fit-self-heating <log with sensor,cpu,reference lines>")
+        }
+    };
+    match std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|log| SelfHeating::fit(&log))
+    {
+        Ok((factor, offset)) => println!("SELF_HEATING={}:{}", factor, offset),
+        Err(e) => eprintln!("Unable to fit self heating parameters: {}", e),
+    }
+}
 
-    let rst = Pin::new(16); //pin 36 //bcm16
-    rst.export().expect("rst export");
-    while !rst.is_exported() {}
-    rst.set_direction(Direction::Out).expect("rst Direction");
-    rst.set_value(1).expect("rst Value set to 1");
+/// Draws a single frame without any display hardware, `--fixture` uses fixed inputs
+/// instead of the weather api, the clock and the sensors
+/// `--gray` previews the screens on a 4 level grayscale panel
+fn render_once<C: Shade>(
+    path: Option<&String>,
+    fixture: bool,
+    scenario: Option<&String>,
+) -> Result<()> {
+    let path = path.ok_or_else(|| {
+        anyhow::anyhow!(
+            "Usage: aer --render-once <out.png|out.pbm> [--gray] [--fixture | --scenario <scenario.json>]"
+        )
+    })?;
+    if fixture {
+        Fixture::pin_env();
+    }
+    SensorConfig::display_sensor(&SENSORS).map_err(anyhow::Error::msg)?;
+    let mut display: Framebuffer<C> = Framebuffer::new(width() as u32, height() as u32);
+
+    if fixture {
+        Fixture::default().draw(&mut display);
+    } else {
+        let scenario = scenario.map(Scenario::load).transpose()?;
+        let (clock, mut provider, mut sensors) = inputs(scenario.as_ref())?;
+        weather(&mut display, provider.as_mut());
+        time(&mut display, clock.as_ref());
+        astronomy(&mut display, clock.as_ref());
+        // not sampled, that would send the readings to influx and mqtt
+        let samples: Vec<Sample> = sensors
+            .iter_mut()
+            .filter_map(ConfiguredSensor::preview)
+            .collect();
+        sensor(&mut display, &samples);
+    }
 
-    let mut delay = Delay {};
+    display.save(path)
+}
 
-    let mut epd = EPD::new(&mut spi, cs, busy, dc, rst, &mut delay).expect("eink initalize error");
+/// Scripted inputs where the scenario has some and the real ones otherwise,
+/// sensors without a script are only faked by the simulator
+fn inputs(
+    scenario: Option<&Scenario>,
+) -> Result<(
+    Box<dyn Clock>,
+    Box<dyn WeatherProvider>,
+    Vec<ConfiguredSensor>,
+)> {
+    let clock: Box<dyn Clock> = match scenario {
+        Some(scenario) => scenario.clock()?,
+        None => Box::new(SystemClock),
+    };
+    let provider: Box<dyn WeatherProvider> = match scenario {
+        Some(scenario) if !scenario.weather.is_empty() || !scenario.forecast.is_empty() => {
+            Box::new(scenario.weather_provider())
+        }
+        _ => Box::new(OpenWeather),
+    };
+    let sensors = SENSORS
+        .iter()
+        .filter_map(|config| {
+            let sensor: Box<dyn Sensor> = match scenario.and_then(|s| s.sensor(&config.name)) {
+                Some(scripted) => Box::new(scripted),
+                #[cfg(feature = "simulator")]
+                None => Box::new(FakeSensor::new(&config.kind)),
+                #[cfg(not(feature = "simulator"))]
+                None => return None,
+            };
+            Some(ConfiguredSensor::new(config, sensor))
+        })
+        .collect();
+
+    Ok((clock, provider, sensors))
+}
 
-    println!("Test all the rotations");
-    let mut display = DisplayEPD::default();
-
-    let i2c = I2cdev::new("/dev/i2c-1").expect("i2cdev device");
-    let mut bme = Bme680::init(i2c, Delay {}, I2CAddress::Secondary).expect("i2cdev device");
-
-    let settings = SettingsBuilder::new()
-        .with_humidity_oversampling(OversamplingSetting::OS2x)
-        .with_pressure_oversampling(OversamplingSetting::OS4x)
-        .with_temperature_oversampling(OversamplingSetting::OS8x)
-        .with_temperature_filter(IIRFilterSize::Size3)
-        .with_gas_measurement(Duration::from_millis(1500), 320, 25)
-        .with_temperature_offset(-2.2)
-        .with_run_gas(true)
-        .build();
+/// The e-paper panel with the spi bus and the pins it is wired to
+#[cfg(not(feature = "simulator"))]
+struct Panel {
+    spi: linux_embedded_hal::Spidev,
+    epd: EPD<linux_embedded_hal::Spidev, AnyPin, AnyPin, AnyPin, AnyPin, linux_embedded_hal::Delay>,
+    delay: linux_embedded_hal::Delay,
+    gpio: GpioBackend,
+    pins: Vec<u64>,
+}
 
-    let profile_dur = bme.get_profile_dur(&settings.0).expect("i2cdev device");
-    info!("Profile duration {:?}", profile_dur);
-    info!("Setting sensor settings");
-    bme.set_sensor_settings(settings).expect("i2cdev device");
-    info!("Setting forced power modes");
-    bme.set_sensor_mode(PowerMode::ForcedMode)
-        .expect("i2cdev device");
-
-    let sensor_settings = bme.get_sensor_settings(settings.1);
-    info!("Sensor settings: {:?}", sensor_settings);
-
-    let power_mode = bme.get_sensor_mode();
-    info!("Sensor power mode: {:?}", power_mode);
-    info!("Setting forced power modes");
-    bme.set_sensor_mode(PowerMode::ForcedMode)
-        .expect("i2cdev device");
-    info!("Retrieving sensor data");
-    let (data, _state) = bme.get_sensor_data().expect("i2cdev device");
-    info!("Sensor Data {:?}", data);
-    info!("Temperature {}°C", data.temperature_celsius());
-    info!("Pressure {}hPa", data.pressure_hpa());
-    info!("Humidity {}%", data.humidity_percent());
-    info!("Gas Resistence {}Ω", data.gas_resistance_ohm());
-
-    #[cfg(feature = "epd2in9")]
-    display.set_rotation(DisplayRotation::Rotate90);
-
-    loop {
-        weather(&mut display);
-
-        time(&mut display);
-
-        if let Err(e) = sensor(&mut display, &mut bme) {
-            error("sensor reading", e);
-        }
+#[cfg(not(feature = "simulator"))]
+impl Panel {
+    fn init(wiring: &Wiring) -> Result<Self, InitError> {
+        use epd_waveshare::prelude::*;
+        use linux_embedded_hal::{sysfs_gpio::Direction, Delay};
+
+        // Configure SPI
+        let mut spi = init_spi(&wiring.spi_device, wiring.spi_speed)?;
+
+        // Configure Digital I/O Pin to be used as Chip Select for SPI,
+        // unless the spi controller drives it
+        let gpio = &wiring.gpio;
+        let cs = match wiring.cs {
+            Some(cs) => open_pin(gpio, cs, Direction::Out, Some(1))?,
+            None => AnyPin::Unused,
+        };
+        let busy = open_pin(gpio, wiring.busy, Direction::In, None)?;
+        let dc = open_pin(gpio, wiring.dc, Direction::Out, Some(1))?;
+        let rst = open_pin(gpio, wiring.rst, Direction::Out, Some(1))?;
+        let pins = wiring.pins().into_iter().map(|(_, pin)| pin).collect();
+
+        let mut delay = Delay {};
+
+        let epd = EPD::new(&mut spi, cs, busy, dc, rst, &mut delay).map_err(InitError::Epd)?;
+        Ok(Self {
+            spi,
+            epd,
+            delay,
+            gpio: gpio.clone(),
+            pins,
+        })
+    }
 
-        if let Err(e) = epd.update_and_display_frame(&mut spi, &display.buffer()) {
+    /// `chromatic` is the red layer of tri-color panels
+    fn show(&mut self, buffer: &[u8], chromatic: Option<&[u8]>) {
+        use epd_waveshare::prelude::*;
+
+        let result = match chromatic {
+            #[cfg(feature = "tricolor")]
+            Some(chromatic) => self
+                .epd
+                .update_color_frame(&mut self.spi, buffer, chromatic)
+                .and_then(|_| self.epd.display_frame(&mut self.spi, &mut self.delay)),
+            _ => self
+                .epd
+                .update_and_display_frame(&mut self.spi, buffer, &mut self.delay),
+        };
+        if let Err(e) = result {
             error("epd update & display", e);
         }
+    }
+
+    /// Deep sleep keeps the last frame without any power, the pins aren't needed anymore
+    fn sleep(mut self) {
+        use epd_waveshare::prelude::*;
+
+        if let Err(e) = self.epd.sleep(&mut self.spi, &mut self.delay) {
+            error("epd sleep", e);
+        }
+        release_pins(&self.gpio, &self.pins);
+    }
+}
+
+#[cfg(not(feature = "simulator"))]
+fn run() -> Result<()> {
+    use epd_waveshare::prelude::*;
+    use log::*;
+
+    env_logger::init();
+    check_config()?;
+
+    // fails early on invalid wiring instead of in the middle of the init
+    let wiring = Wiring::from_env()?;
+    info!("Wiring: {:?}", wiring);
+
+    status_influx(Status::STARTUP, None);
+    status_mqtt(&Status::STARTUP);
+
+    // without a panel the readings are still sent and available via http
+    let mut panel = match *OUTPUT {
+        Output::Epd => match Panel::init(&wiring) {
+            Ok(panel) => Some(panel),
+            Err(e) => {
+                error("display init", e);
+                None
+            }
+        },
+        _ => None,
+    };
+
+    println!("Test all the rotations");
+    let mut display = Mirrored::new(DisplayEPD::default());
+
+    // sensors which can't be initialized are left out, the display runs without them
+    let sensors: Vec<ConfiguredSensor> = SENSORS
+        .iter()
+        .filter_map(|config| {
+            let sensor: Box<dyn Sensor> = match &config.kind {
+                SensorKind::Bme680 { address, settings } => {
+                    match init_bme680(&wiring.i2c_device, *address, settings) {
+                        Ok(bme) => Box::new(bme),
+                        Err(e) => {
+                            error(&format!("sensor {} init", config.name), e);
+                            return None;
+                        }
+                    }
+                }
+                SensorKind::OneWire { id } => Box::new(OneWireSensor::new(id)),
+            };
+            Some(ConfiguredSensor::new(config, sensor))
+        })
+        .collect();
+    let sampler = Sampler::start(sensors, *SAMPLE_INTERVAL);
+    let mut provider = OpenWeather;
+
+    rotate(&mut display.inner);
+
+    let (command_sender, commands) = mpsc::channel();
+    if let Some(addr) = &*HTTP_BIND {
+        serve(addr, command_sender.clone())?;
+    }
+    forward_signals(command_sender)?;
+    let mut screen = Screen::default();
+
+    let reason = loop {
+        let samples = sampler.take();
+        screen.draw(&mut display, &mut provider, &SystemClock, &samples);
+
+        #[cfg(feature = "tricolor")]
+        let chromatic = chromatic_layer(screen.page(), &samples);
+        if let Some(panel) = &mut panel {
+            #[cfg(feature = "tricolor")]
+            panel.show(display.inner.buffer(), Some(chromatic.buffer()));
+            #[cfg(not(feature = "tricolor"))]
+            panel.show(display.inner.buffer(), None);
+        }
+        if let Err(e) = OUTPUT.show(&display.frame) {
+            error("output", e);
+        }
+        state().refreshed(samples, &display.frame);
 
         //thread::sleep(Duration::from_millis(3000));
-        thread::sleep(Duration::from_secs(60));
+        match wait_for_command(&commands, &mut screen, Duration::from_secs(60)) {
+            Some(Exit::Shutdown(reason)) => break reason,
+            Some(Exit::Reload) => return Err(reload(sampler).into()),
+            None => {}
+        }
+    };
+
+    info!("Shutting down: {}", reason);
+    draw_offline(&mut display, chrono::Local::now());
+    if let Some(mut panel) = panel {
+        #[cfg(feature = "tricolor")]
+        panel.show(display.inner.buffer(), Some(DisplayEPD::default().buffer()));
+        #[cfg(not(feature = "tricolor"))]
+        panel.show(display.inner.buffer(), None);
+        panel.sleep();
+    }
+    if let Err(e) = OUTPUT.show(&display.frame) {
+        error("output", e);
+    }
+    shutdown(sampler, reason);
+
+    Ok(())
+}
+
+/// Portrait panels are used in landscape
+#[cfg(not(feature = "simulator"))]
+fn rotate(display: &mut DisplayEPD) {
+    #[cfg(any(feature = "epd2in13", feature = "epd2in9", feature = "epd2in9bc"))]
+    {
+        use epd_waveshare::prelude::*;
+
+        display.set_rotation(DisplayRotation::Rotate90);
     }
+    #[cfg(not(any(feature = "epd2in13", feature = "epd2in9", feature = "epd2in9bc")))]
+    let _ = display;
+}
+
+/// Red layer of tri-color panels, only the overview shows warnings
+#[cfg(all(feature = "tricolor", not(feature = "simulator")))]
+fn chromatic_layer(page: Page, samples: &[Sample]) -> DisplayEPD {
+    let mut layer = DisplayEPD::default();
+    rotate(&mut layer);
+    if page == Page::Overview {
+        let weather = state().weather;
+        draw_warnings(&mut layer, &Warning::active(weather.as_ref(), samples));
+    }
+    layer
 }
 
 #[cfg(feature = "simulator")]
@@ -138,31 +462,63 @@ pub fn run() -> Result<()> {
     use embedded_graphics::geometry::Size;
     use embedded_graphics_simulator::*;
 
+    check_config()?;
     status_influx(Status::STARTUP, None);
+    status_mqtt(&Status::STARTUP);
 
-    let mut display = SimulatorDisplay::new(Size::new(width() as u32, height() as u32));
+    let mut display = Mirrored::new(SimulatorDisplay::new(Size::new(
+        width() as u32,
+        height() as u32,
+    )));
     let output_settings = OutputSettingsBuilder::new()
         //.theme(BinaryColorTheme::LcdWhite)
         .scale(1)
         .build();
     let mut window = Window::new("Nidus", &output_settings);
 
-    'running: loop {
-        weather(&mut display);
-
-        time(&mut display);
-
-        if let Err(e) = sensor(&mut display) {
-            error("sensor reading", e);
+    // SCENARIO=scenario.json replays scripted inputs, see `Scenario`
+    let scenario = dotenv::var("SCENARIO")
+        .ok()
+        .map(Scenario::load)
+        .transpose()?;
+    let (clock, mut provider, sensors) = inputs(scenario.as_ref())?;
+    let sampler = Sampler::start(sensors, *SAMPLE_INTERVAL);
+
+    let (command_sender, commands) = mpsc::channel();
+    if let Some(addr) = &*HTTP_BIND {
+        serve(addr, command_sender.clone())?;
+    }
+    forward_signals(command_sender)?;
+    let mut screen = Screen::default();
+
+    let reason = loop {
+        let samples = sampler.take();
+        screen.draw(&mut display, provider.as_mut(), clock.as_ref(), &samples);
+        // the simulator has no red, the warnings are drawn in black instead
+        #[cfg(feature = "tricolor")]
+        {
+            if screen.page() == Page::Overview {
+                let weather = state().weather;
+                draw_warnings(&mut display, &Warning::active(weather.as_ref(), &samples));
+            }
         }
 
-        window.update(&display);
+        window.update(&display.inner);
+        state().refreshed(samples, &display.frame);
         if window.events().any(|e| e == SimulatorEvent::Quit) {
-            break 'running;
+            break "window closed".to_string();
         }
 
-        thread::sleep(Duration::from_millis(3000));
-    }
+        match wait_for_command(&commands, &mut screen, Duration::from_millis(3000)) {
+            Some(Exit::Shutdown(reason)) => break reason,
+            Some(Exit::Reload) => return Err(reload(sampler).into()),
+            None => {}
+        }
+    };
+
+    draw_offline(&mut display, clock.now());
+    window.update(&display.inner);
+    shutdown(sampler, reason);
 
     Ok(())
 }
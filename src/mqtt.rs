@@ -0,0 +1,208 @@
+use crate::*;
+use lazy_static::lazy_static;
+use rumqttc::{Client, Event, LastWill, MqttOptions, Outgoing, Packet, QoS};
+use serde_json::json;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Mutex;
+use std::thread;
+
+/// Connection to the broker configured with `MQTT_HOST`
+pub struct Mqtt {
+    client: Mutex<Client>,
+    device: String,
+    /// signals that the disconnect went out
+    disconnected: Mutex<Receiver<()>>,
+}
+
+lazy_static! {
+    pub static ref MQTT: Option<Mqtt> = Mqtt::from_env();
+}
+
+impl Mqtt {
+    /// `MQTT_HOST`, `MQTT_PORT`, `MQTT_USER`, `MQTT_PASSWORD` and `MQTT_DEVICE_ID` (defaults to aer)
+    fn from_env() -> Option<Self> {
+        let host = dotenv::var("MQTT_HOST").ok()?;
+        let port = dotenv::var("MQTT_PORT")
+            .map(|p| p.parse().expect("MQTT_PORT number"))
+            .unwrap_or(1883);
+        let device = dotenv::var("MQTT_DEVICE_ID").unwrap_or_else(|_| "aer".into());
+
+        let mut options = MqttOptions::new(device.clone(), host, port);
+        if let (Ok(user), Ok(password)) = (dotenv::var("MQTT_USER"), dotenv::var("MQTT_PASSWORD")) {
+            options.set_credentials(user, password);
+        }
+        options.set_last_will(LastWill::new(
+            availability_topic(&device),
+            Status::SHUTDOWN.availability(),
+            QoS::AtLeastOnce,
+            true,
+        ));
+
+        let (client, mut connection) = Client::new(options, 64);
+        let mut reconnected = client.clone();
+        let availability = availability_topic(&device);
+        let (done, disconnected) = mpsc::channel();
+        thread::spawn(move || {
+            for notification in connection.iter() {
+                match notification {
+                    // the last will might have been sent in between. This thread drives the
+                    // eventloop, a blocking publish here could wait on a full request queue forever
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        if let Err(e) = reconnected.try_publish(
+                            availability.as_str(),
+                            QoS::AtLeastOnce,
+                            true,
+                            Status::STARTUP.availability(),
+                        ) {
+                            log::error!("Unable to publish to {}: {}", availability, e);
+                        }
+                        for tile in TILES.iter() {
+                            if let Err(e) =
+                                reconnected.try_subscribe(tile.topic.as_str(), QoS::AtMostOnce)
+                            {
+                                log::error!("Unable to subscribe to {}: {}", tile.topic, e);
+                            }
+                        }
+                    }
+                    Ok(Event::Incoming(Packet::Publish(publish))) => {
+                        tile_message(&publish.topic, &publish.payload)
+                    }
+                    Ok(Event::Outgoing(Outgoing::Disconnect)) => {
+                        let _ = done.send(());
+                        return;
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        log::warn!("Mqtt connection: {}", e);
+                        thread::sleep(std::time::Duration::from_secs(5));
+                    }
+                }
+            }
+        });
+
+        let mqtt = Self {
+            client: Mutex::new(client),
+            device,
+            disconnected: Mutex::new(disconnected),
+        };
+        mqtt.discovery();
+        Some(mqtt)
+    }
+
+    fn publish(&self, topic: &str, retain: bool, payload: String) {
+        let mut client = self.client.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = client.publish(topic, QoS::AtLeastOnce, retain, payload) {
+            log::error!("Unable to publish to {}: {}", topic, e);
+        }
+    }
+
+    fn state_topic(&self, sensor: &str) -> String {
+        format!("aer/{}/{}/state", self.device, sensor)
+    }
+
+    /// Home Assistant discovery configs, so the sensors show up without any yaml
+    fn discovery(&self) {
+        let prefix =
+            dotenv::var("MQTT_DISCOVERY_PREFIX").unwrap_or_else(|_| "homeassistant".into());
+        let device = json!({
+            "identifiers": [self.device],
+            "name": format!("aer {}", self.device),
+            "model": "ePaper-Thermometer",
+            "manufacturer": "aer",
+        });
+
+        let temperature = METRICS_UNITS.temperature.symbol();
+        let pressure = METRICS_UNITS.pressure.symbol();
+        for config in SENSORS.iter() {
+            let quantities: &[(&str, &str, &str)] = match config.kind {
+                SensorKind::Bme680 { .. } => &[
+                    ("temperature", "temperature", temperature),
+                    ("humidity", "humidity", "%"),
+                    ("pressure", "pressure", pressure),
+                    ("gas_resistance", "", "Ω"),
+                    ("iaq", "", "IAQ"),
+                ],
+                SensorKind::OneWire { .. } => &[("temperature", "temperature", temperature)],
+            };
+            for (quantity, device_class, unit) in quantities {
+                let unique_id = format!("{}_{}_{}", self.device, config.name, quantity);
+                let mut payload = json!({
+                    "name": format!("{} {}", config.name, quantity),
+                    "unique_id": unique_id,
+                    "state_topic": self.state_topic(&config.name),
+                    "value_template": format!("{{{{ value_json.{} }}}}", quantity),
+                    "unit_of_measurement": unit,
+                    "availability_topic": availability_topic(&self.device),
+                    "device": device,
+                });
+                if !device_class.is_empty() {
+                    payload["device_class"] = json!(device_class);
+                }
+                self.publish(
+                    &format!("{}/sensor/{}/config", prefix, unique_id),
+                    true,
+                    payload.to_string(),
+                );
+            }
+        }
+    }
+}
+
+fn availability_topic(device: &str) -> String {
+    format!("aer/{}/status", device)
+}
+
+impl Status {
+    fn availability(&self) -> &'static str {
+        match self {
+            Status::STARTUP => "online",
+            Status::SHUTDOWN => "offline",
+        }
+    }
+}
+
+pub fn status_mqtt(status: &Status) {
+    if let Some(mqtt) = &*MQTT {
+        mqtt.publish(
+            &availability_topic(&mqtt.device),
+            true,
+            status.availability().into(),
+        );
+    }
+}
+
+/// Waits until everything published so far went out, otherwise the SHUTDOWN status gets lost on exit
+pub fn disconnect_mqtt() {
+    if let Some(mqtt) = &*MQTT {
+        if let Err(e) = mqtt
+            .client
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .disconnect()
+        {
+            log::error!("Unable to disconnect from mqtt: {}", e);
+            return;
+        }
+        let disconnected = mqtt.disconnected.lock().unwrap_or_else(|e| e.into_inner());
+        if disconnected
+            .recv_timeout(std::time::Duration::from_secs(5))
+            .is_err()
+        {
+            log::warn!("Mqtt didn't disconnect in time");
+        }
+    }
+}
+
+pub(crate) fn reading_to_mqtt(config: &SensorConfig, reading: &Reading) {
+    if let Some(mqtt) = &*MQTT {
+        let reading = METRICS_UNITS.convert(reading);
+        let payload = json!({
+            "temperature": reading.temperature,
+            "humidity": reading.humidity,
+            "pressure": reading.pressure,
+            "gas_resistance": reading.gas_resistance,
+            "iaq": reading.iaq(),
+        });
+        mqtt.publish(&mqtt.state_topic(&config.name), false, payload.to_string());
+    }
+}
@@ -0,0 +1,50 @@
+use crate::*;
+use std::fs;
+use std::path::PathBuf;
+
+/// DS18B20 and compatible probes read through the w1-gpio/w1-therm kernel modules
+///
+/// activate with `dtoverlay=w1-gpio` in /boot/config.txt
+pub struct OneWireSensor {
+    path: PathBuf,
+}
+
+impl OneWireSensor {
+    pub fn new(id: &str) -> Self {
+        Self {
+            path: PathBuf::from("/sys/bus/w1/devices")
+                .join(id)
+                .join("w1_slave"),
+        }
+    }
+}
+
+impl Sensor for OneWireSensor {
+    fn read(&mut self) -> Result<Reading, String> {
+        let content = fs::read_to_string(&self.path)
+            .map_err(|e| format!("Unable to read {}: {}", self.path.display(), e))?;
+
+        // e.g.
+        // 72 01 4b 46 7f ff 0e 10 57 : crc=57 YES
+        // 72 01 4b 46 7f ff 0e 10 57 t=23125
+        let mut lines = content.lines();
+        if !lines
+            .next()
+            .map_or(false, |l| l.trim_end().ends_with("YES"))
+        {
+            return Err(format!("CRC check failed: {:?}", content));
+        }
+        let millis: i32 = lines
+            .next()
+            .and_then(|l| l.rsplit("t=").next())
+            .and_then(|t| t.trim().parse().ok())
+            .ok_or_else(|| format!("Received bad sensor data: {:?}", content))?;
+
+        Ok(Reading {
+            temperature: millis as f32 / 1000.0,
+            humidity: None,
+            pressure: None,
+            gas_resistance: None,
+        })
+    }
+}
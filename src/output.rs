@@ -0,0 +1,130 @@
+use crate::*;
+use anyhow::{anyhow, Result};
+use std::fs::{self, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Where the frames are shown, `OUTPUT=epd|fb[:<device>]|terminal`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+    /// the e-paper panel chosen by the features
+    Epd,
+    /// a linux framebuffer like HDMI, defaults to `/dev/fb0`
+    Fb(PathBuf),
+    /// ANSI half blocks on stdout, e.g. over ssh
+    Terminal,
+}
+
+impl Output {
+    pub fn from_env() -> Self {
+        let output = match dotenv::var("OUTPUT") {
+            Ok(output) => output,
+            Err(_) => return Output::Epd,
+        };
+        let mut parts = output.splitn(2, ':');
+        match (parts.next().unwrap_or_default(), parts.next()) {
+            ("epd", None) => Output::Epd,
+            ("fb", None) => Output::Fb("/dev/fb0".into()),
+            ("fb", Some(device)) => Output::Fb(device.into()),
+            ("terminal", None) => Output::Terminal,
+            _ => panic!(
+                "invalid OUTPUT '{}', expected epd, fb[:<device>] or terminal",
+                output
+            ),
+        }
+    }
+
+    /// Nothing to do for `Output::Epd`, the panel has its own buffer
+    pub fn show<C: Shade>(&self, frame: &Framebuffer<C>) -> Result<()> {
+        match self {
+            Output::Epd => Ok(()),
+            Output::Fb(device) => write_fb(device, frame),
+            Output::Terminal => {
+                let stdout = std::io::stdout();
+                let mut out = stdout.lock();
+                // back to the top left instead of scrolling
+                out.write_all(b"\x1b[H\x1b[J")?;
+                frame.write_ansi(&mut out)?;
+                out.flush()?;
+                Ok(())
+            }
+        }
+    }
+}
+
+struct FbInfo {
+    width: u32,
+    height: u32,
+    bits_per_pixel: u32,
+    stride: u32,
+}
+
+impl FbInfo {
+    /// Geometry from `/sys/class/graphics/<fbN>`
+    fn read(device: &Path) -> Result<Self> {
+        let name = device
+            .file_name()
+            .ok_or_else(|| anyhow!("no framebuffer name in {}", device.display()))?;
+        let sys = Path::new("/sys/class/graphics").join(name);
+        let read = |file: &str| -> Result<String> {
+            Ok(fs::read_to_string(sys.join(file))?.trim().to_string())
+        };
+
+        let size = read("virtual_size")?;
+        let mut size = size.splitn(2, ',').map(str::parse::<u32>);
+        let (width, height) = match (size.next(), size.next()) {
+            (Some(Ok(width)), Some(Ok(height))) => (width, height),
+            _ => return Err(anyhow!("invalid virtual_size of {}", device.display())),
+        };
+        Ok(Self {
+            width,
+            height,
+            bits_per_pixel: read("bits_per_pixel")?.parse()?,
+            stride: read("stride")?.parse()?,
+        })
+    }
+
+    /// XRGB8888 or RGB565
+    fn pixel<C: Shade>(&self, color: C) -> Result<Vec<u8>> {
+        let b = color.brightness();
+        Ok(match self.bits_per_pixel {
+            32 => vec![b, b, b, 0xff],
+            16 => {
+                let rgb565 =
+                    (u16::from(b >> 3) << 11) | (u16::from(b >> 2) << 5) | u16::from(b >> 3);
+                rgb565.to_le_bytes().to_vec()
+            }
+            bpp => return Err(anyhow!("{} bits per pixel aren't supported", bpp)),
+        })
+    }
+}
+
+/// Scaled up as far as it fits and centered
+fn write_fb<C: Shade>(device: &Path, frame: &Framebuffer<C>) -> Result<()> {
+    let info = FbInfo::read(device)?;
+    let size = frame.size();
+    let scale = (info.width / size.width)
+        .min(info.height / size.height)
+        .max(1);
+    let left = info.width.saturating_sub(size.width * scale) / 2;
+    let top = info.height.saturating_sub(size.height * scale) / 2;
+    let bytes_per_pixel = info.bits_per_pixel / 8;
+
+    let mut fb = OpenOptions::new().write(true).open(device)?;
+    let mut row = Vec::with_capacity((size.width * scale * bytes_per_pixel) as usize);
+    for y in 0..size.height.min(info.height / scale) {
+        row.clear();
+        for x in 0..size.width.min(info.width / scale) {
+            let pixel = info.pixel(frame.pixel(x, y))?;
+            for _ in 0..scale {
+                row.extend_from_slice(&pixel);
+            }
+        }
+        for line in 0..scale {
+            let offset = (top + y * scale + line) * info.stride + left * bytes_per_pixel;
+            fb.seek(SeekFrom::Start(offset as u64))?;
+            fb.write_all(&row)?;
+        }
+    }
+    Ok(())
+}
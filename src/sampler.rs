@@ -0,0 +1,213 @@
+use crate::*;
+use serde::Serialize;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How readings are sent to influx
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SensorMetrics {
+    /// every single sample
+    Raw,
+    /// min/mean/max over the given window
+    Aggregated(Duration),
+}
+
+impl SensorMetrics {
+    /// `METRICS_WINDOW=raw|aggregate[:<secs>]`, the window defaults to 60s
+    pub fn from_env() -> Self {
+        let metrics = match dotenv::var("METRICS_WINDOW") {
+            Ok(metrics) => metrics,
+            Err(_) => return SensorMetrics::Raw,
+        };
+        let mut parts = metrics.splitn(2, ':');
+        match (parts.next().unwrap_or_default(), parts.next()) {
+            ("raw", None) => SensorMetrics::Raw,
+            ("aggregate", None) => SensorMetrics::Aggregated(Duration::from_secs(60)),
+            ("aggregate", Some(secs)) => match secs.parse() {
+                Ok(secs) => SensorMetrics::Aggregated(Duration::from_secs(secs)),
+                Err(e) => panic!("invalid METRICS_WINDOW seconds '{}': {}", secs, e),
+            },
+            _ => panic!("invalid METRICS_WINDOW '{}'", metrics),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Aggregate {
+    pub min: Reading,
+    pub mean: Reading,
+    pub max: Reading,
+    pub count: usize,
+}
+
+impl Aggregate {
+    pub fn of(readings: &[Reading]) -> Option<Self> {
+        let first = *readings.first()?;
+        let (mut min, mut mean, mut max) = (first, first, first);
+        for q in Quantity::ALL.iter() {
+            let values: Vec<f32> = readings.iter().filter_map(|r| q.get(r)).collect();
+            if values.is_empty() {
+                continue;
+            }
+            q.set(
+                &mut min,
+                values.iter().copied().fold(std::f32::MAX, f32::min),
+            );
+            q.set(
+                &mut max,
+                values.iter().copied().fold(std::f32::MIN, f32::max),
+            );
+            q.set(&mut mean, values.iter().sum::<f32>() / values.len() as f32);
+        }
+        Some(Self {
+            min,
+            mean,
+            max,
+            count: readings.len(),
+        })
+    }
+}
+
+/// Latest reading of a sensor and the aggregate since the last display refresh
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Sample {
+    pub name: String,
+    pub latest: Reading,
+    pub aggregate: Option<Aggregate>,
+}
+
+struct Slot {
+    name: String,
+    latest: Option<Reading>,
+    since_refresh: Vec<Reading>,
+}
+
+struct Worker {
+    sensors: Vec<ConfiguredSensor>,
+    slots: Arc<Mutex<Vec<Slot>>>,
+    window: Vec<Vec<Reading>>,
+    /// readings in the window with a suspicious quantity, per sensor
+    window_suspicious: Vec<usize>,
+    window_start: Instant,
+}
+
+impl Worker {
+    fn round(&mut self) {
+        for (i, s) in self.sensors.iter_mut().enumerate() {
+            let validated = match s.sample() {
+                Some(validated) => validated,
+                None => continue,
+            };
+            let reading = validated.reading;
+            reading_to_mqtt(&s.config, &reading);
+
+            let suspicious = !validated.suspicious.is_empty();
+            match *METRICS_WINDOW {
+                SensorMetrics::Raw => sensor_to_influx(&s.config, &reading, suspicious),
+                SensorMetrics::Aggregated(_) => {
+                    self.window[i].push(reading);
+                    self.window_suspicious[i] += suspicious as usize;
+                }
+            }
+
+            let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+            slots[i].latest = Some(reading);
+            slots[i].since_refresh.push(reading);
+        }
+
+        if let SensorMetrics::Aggregated(window) = *METRICS_WINDOW {
+            if self.window_start.elapsed() >= window {
+                self.flush();
+            }
+        }
+    }
+
+    /// Sends the aggregates of the current window, even if it isn't full yet
+    fn flush(&mut self) {
+        let windows = self
+            .window
+            .iter_mut()
+            .zip(self.window_suspicious.iter_mut());
+        for (s, (readings, suspicious)) in self.sensors.iter().zip(windows) {
+            if let Some(aggregate) = Aggregate::of(readings) {
+                aggregate_to_influx(&s.config, &aggregate, *suspicious);
+            }
+            readings.clear();
+            *suspicious = 0;
+        }
+        self.window_start = Instant::now();
+    }
+}
+
+/// Reads the sensors every `SAMPLE_INTERVAL` seconds, independent of the display refresh
+pub struct Sampler {
+    slots: Arc<Mutex<Vec<Slot>>>,
+    stop: Sender<()>,
+    worker: JoinHandle<()>,
+}
+
+impl Sampler {
+    /// Takes the first samples right away, so they are available for the first refresh
+    pub fn start(sensors: Vec<ConfiguredSensor>, interval: Duration) -> Self {
+        let slots = Arc::new(Mutex::new(
+            sensors
+                .iter()
+                .map(|s| Slot {
+                    name: s.config.name.clone(),
+                    latest: None,
+                    since_refresh: Vec::new(),
+                })
+                .collect(),
+        ));
+        let mut worker = Worker {
+            window: vec![Vec::new(); sensors.len()],
+            window_suspicious: vec![0; sensors.len()],
+            sensors,
+            slots: slots.clone(),
+            window_start: Instant::now(),
+        };
+        worker.round();
+
+        let (stop, stopped) = mpsc::channel();
+        let worker = thread::spawn(move || loop {
+            match stopped.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => worker.round(),
+                _ => return worker.flush(),
+            }
+        });
+
+        Self {
+            slots,
+            stop,
+            worker,
+        }
+    }
+
+    /// Stops sampling and sends what is still pending in the aggregation window
+    pub fn stop(self) {
+        let _ = self.stop.send(());
+        if self.worker.join().is_err() {
+            log::error!("Sampler thread panicked");
+        }
+    }
+
+    /// Latest reading of every sensor with min/mean/max since the previous call
+    pub fn take(&self) -> Vec<Sample> {
+        let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        slots
+            .iter_mut()
+            .filter_map(|slot| {
+                let latest = slot.latest?;
+                let aggregate = Aggregate::of(&slot.since_refresh);
+                slot.since_refresh.clear();
+                Some(Sample {
+                    name: slot.name.clone(),
+                    latest,
+                    aggregate,
+                })
+            })
+            .collect()
+    }
+}
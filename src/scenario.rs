@@ -0,0 +1,137 @@
+use crate::*;
+use anyhow::Result;
+use serde::Deserialize;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// A scripted value or a scripted failure, e.g. `{"error": "api down"}`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Step<T> {
+    Err { error: String },
+    Ok(T),
+}
+
+/// Returns one step per call, the last one repeats forever
+#[derive(Debug, Clone)]
+pub struct Script<T> {
+    steps: Vec<Step<T>>,
+    next: usize,
+}
+
+impl<T: Clone> Script<T> {
+    pub fn new(steps: Vec<Step<T>>) -> Self {
+        Self { steps, next: 0 }
+    }
+
+    pub fn advance(&mut self) -> Result<T, String> {
+        let step = self
+            .steps
+            .get(self.next.min(self.steps.len().saturating_sub(1)))
+            .ok_or_else(|| "nothing scripted".to_string())?;
+        self.next += 1;
+        match step {
+            Step::Ok(value) => Ok(value.clone()),
+            Step::Err { error } => Err(error.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClockScript {
+    /// RFC 3339, e.g. `2020-03-14T23:58:00+01:00`
+    pub start: String,
+    /// advance per refresh
+    #[serde(default)]
+    pub step_secs: i64,
+}
+
+/// Inputs for the simulator and `--render-once` without network or hardware
+///
+/// ```json
+/// {
+///   "clock": { "start": "2020-12-31T23:59:00+01:00", "step_secs": 60 },
+///   "weather": [{ "temperature": -12.5, "sunrise": 1609401600, "sunset": 1609430400 }, { "error": "api down" }],
+///   "forecast": [[{ "time": 1609459200, "temp": -10.0, "temp_min": -11.0, "temp_max": -9.0 }]],
+///   "sensors": { "bme680": [{ "temperature": 19.5, "humidity": 40.0 }] }
+/// }
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct Scenario {
+    pub clock: Option<ClockScript>,
+    #[serde(default)]
+    pub weather: Vec<Step<Weather>>,
+    #[serde(default)]
+    pub forecast: Vec<Step<Vec<ForecastSlot>>>,
+    #[serde(default)]
+    pub sensors: HashMap<String, Vec<Step<Reading>>>,
+}
+
+impl Scenario {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Falls back to the system clock if nothing is scripted
+    pub fn clock(&self) -> Result<Box<dyn Clock>> {
+        match &self.clock {
+            Some(script) => Ok(Box::new(ScriptedClock {
+                start: DateTime::parse_from_rfc3339(&script.start)?.with_timezone(&Local),
+                step: chrono::Duration::seconds(script.step_secs),
+                calls: Cell::new(0),
+            })),
+            None => Ok(Box::new(SystemClock)),
+        }
+    }
+
+    pub fn weather_provider(&self) -> ScriptedWeather {
+        ScriptedWeather {
+            current: Script::new(self.weather.clone()),
+            forecast: Script::new(self.forecast.clone()),
+        }
+    }
+
+    pub fn sensor(&self, name: &str) -> Option<ScriptedSensor> {
+        self.sensors
+            .get(name)
+            .map(|steps| ScriptedSensor(Script::new(steps.clone())))
+    }
+}
+
+pub struct ScriptedClock {
+    start: DateTime<Local>,
+    step: chrono::Duration,
+    calls: Cell<i32>,
+}
+
+impl Clock for ScriptedClock {
+    fn now(&self) -> DateTime<Local> {
+        let calls = self.calls.get();
+        self.calls.set(calls + 1);
+        self.start + self.step * calls
+    }
+}
+
+pub struct ScriptedWeather {
+    current: Script<Weather>,
+    forecast: Script<Vec<ForecastSlot>>,
+}
+
+impl WeatherProvider for ScriptedWeather {
+    fn current(&mut self) -> Result<Weather, String> {
+        self.current.advance()
+    }
+
+    fn forecast(&mut self) -> Result<Vec<ForecastSlot>, String> {
+        self.forecast.advance()
+    }
+}
+
+pub struct ScriptedSensor(Script<Reading>);
+
+impl Sensor for ScriptedSensor {
+    fn read(&mut self) -> Result<Reading, String> {
+        self.0.advance()
+    }
+}
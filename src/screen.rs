@@ -0,0 +1,220 @@
+use crate::*;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// Sent to the main loop, e.g. by the http api
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Refresh,
+    Page(Page),
+    Message {
+        text: String,
+        duration: Duration,
+    },
+    ReloadConfig,
+    /// e.g. on SIGTERM, the reason is reported with the SHUTDOWN status
+    Shutdown(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum Page {
+    /// weather, forecast, time and the main sensor
+    Overview,
+    /// all sensors with all their values
+    Sensors,
+    /// values subscribed via mqtt
+    Tiles,
+}
+
+impl Page {
+    pub fn from_index(index: usize) -> Option<Page> {
+        match index {
+            0 => Some(Page::Overview),
+            1 => Some(Page::Sensors),
+            2 => Some(Page::Tiles),
+            _ => None,
+        }
+    }
+
+    /// `PAGES=0,2` are shown in turn, one per refresh, the overview only by default
+    pub fn rotation_from_env() -> Vec<Page> {
+        let pages = match dotenv::var("PAGES") {
+            Ok(pages) => pages,
+            Err(_) => return vec![Page::Overview],
+        };
+        let rotation: Vec<Page> = pages
+            .split(',')
+            .map(str::trim)
+            .filter(|page| !page.is_empty())
+            .map(|page| {
+                page.parse()
+                    .ok()
+                    .and_then(Page::from_index)
+                    .unwrap_or_else(|| {
+                        panic!("invalid page '{}' in PAGES, expected 0, 1 or 2", page)
+                    })
+            })
+            .collect();
+        if rotation.is_empty() {
+            panic!("PAGES needs at least one page");
+        }
+        rotation
+    }
+}
+
+/// Page and temporary message currently shown
+pub struct Screen {
+    page: Page,
+    message: Option<(String, Instant)>,
+    clear: bool,
+    /// the page was shown, the next refresh moves on to the next of the `PAGES`
+    shown: bool,
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Self {
+            page: PAGES[0],
+            message: None,
+            clear: false,
+            shown: false,
+        }
+    }
+}
+
+impl Screen {
+    pub fn page(&self) -> Page {
+        self.page
+    }
+
+    /// Everything but `Command::ReloadConfig` and `Command::Shutdown`, which need the main loop
+    pub fn apply(&mut self, command: Command) {
+        match command {
+            Command::Page(page) if page != self.page => {
+                self.page = page;
+                self.clear = true;
+                self.shown = false;
+            }
+            Command::Message { text, duration } => {
+                self.message = Some((text, Instant::now() + duration));
+            }
+            _ => {}
+        }
+    }
+
+    pub fn draw<C: Shade, T: DrawTarget<C>>(
+        &mut self,
+        display: &mut T,
+        provider: &mut dyn WeatherProvider,
+        clock: &dyn Clock,
+        samples: &[Sample],
+    ) {
+        if let Some((_, until)) = &self.message {
+            if Instant::now() >= *until {
+                self.message = None;
+                self.clear = true;
+            }
+        }
+        if self.shown {
+            self.rotate();
+        }
+        if self.clear {
+            let _ = display.clear(C::white());
+            self.clear = false;
+        }
+
+        match self.page {
+            Page::Overview => {
+                weather(display, provider);
+                time(display, clock);
+                astronomy(display, clock);
+                sensor(display, samples);
+            }
+            Page::Sensors => sensor_page(display, samples),
+            Page::Tiles => draw_tiles(display),
+        }
+
+        if let Some((text, _)) = &self.message {
+            draw_message(display, text);
+        }
+        state().page = Some(self.page);
+        self.shown = true;
+    }
+
+    /// Moves on to the next of the `PAGES`, a page chosen over the http api
+    /// which isn't one of them stays
+    fn rotate(&mut self) {
+        if let Some(i) = PAGES.iter().position(|page| *page == self.page) {
+            let next = PAGES[(i + 1) % PAGES.len()];
+            if next != self.page {
+                self.page = next;
+                self.clear = true;
+            }
+        }
+    }
+}
+
+fn sensor_page<C: Shade, T: DrawTarget<C>>(display: &mut T, samples: &[Sample]) {
+    for (i, sample) in samples.iter().enumerate() {
+        let y = 36 * i as i32;
+        text_12x16(
+            display,
+            &format!(
+                "{:10.10} {:>7}",
+                sample.name,
+                UNITS.format_temperature(sample.latest.temperature, 1)
+            ),
+            (0, y).into(),
+        );
+        let mut details = Vec::new();
+        if let Some(humidity) = sample.latest.humidity {
+            details.push(format!("{}%", LOCALE.number(humidity, 1)));
+        }
+        if let Some(pressure) = sample.latest.pressure {
+            details.push(UNITS.format_pressure(pressure));
+        }
+        if let Some(gas_resistance) = sample.latest.gas_resistance {
+            details.push(format!(
+                "{}kOhm",
+                LOCALE.number(gas_resistance as f32 / 1000.0, 1)
+            ));
+        }
+        text_6x8(display, &details.join("  "), (0, y + 18).into());
+    }
+}
+
+/// Last screen before shutting down, it stays visible while the panel sleeps
+pub fn draw_offline<C: Shade, T: DrawTarget<C>>(display: &mut T, since: DateTime<Local>) {
+    let since = since.with_timezone(&*TIMEZONE);
+    let _ = display.clear(C::white());
+    let half = height() / 2;
+    let (offline, since_label) = LOCALE.offline_since();
+    text_aligned(
+        display,
+        TextFont::F24x32,
+        offline,
+        area(Point::zero(), width(), half),
+        HAlign::Center,
+        VAlign::Bottom,
+    );
+    text_aligned(
+        display,
+        TextFont::F8x16,
+        &format!(
+            "{} {} {}",
+            since_label,
+            LOCALE.day_and_month(&since),
+            TIME_FORMAT.time(&since)
+        ),
+        area((0, half + 8).into(), width(), height() - half - 8),
+        HAlign::Center,
+        VAlign::Top,
+    );
+}
+
+fn draw_message<C: Shade, T: DrawTarget<C>>(display: &mut T, text: &str) {
+    let top = height() - 24;
+    let _ = rectangle((0, top).into(), (width() - 1, height() - 1).into()).draw(display);
+    let _ = line((0, top).into(), (width() - 1, top).into()).draw(display);
+    text_8x16(display, text, (4, top + 4).into());
+}
@@ -6,101 +6,452 @@ use bme680::{Bme680, *};
 use embedded_hal::blocking::delay::DelayMs;
 #[cfg(not(feature = "simulator"))]
 use embedded_hal::blocking::i2c::{Read, Write};
-#[cfg(not(feature = "simulator"))]
 use log::*;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+/// A single measurement, quantities the sensor can't measure are `None`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Reading {
+    pub temperature: f32,
+    pub humidity: Option<f32>,
+    pub pressure: Option<f32>,
+    pub gas_resistance: Option<u32>,
+}
+
+impl Reading {
+    /// Rough indoor air quality approximation from 0 (good) to 500 (bad), weighting
+    /// humidity 25% and gas resistance 75%, not the calibrated IAQ of Bosch's BSEC library
+    pub fn iaq(&self) -> Option<f32> {
+        let humidity = self.humidity?;
+        let gas = self.gas_resistance? as f32;
+
+        let humidity_score = if humidity < 40.0 {
+            humidity / 40.0 * 25.0
+        } else {
+            (100.0 - humidity) / 60.0 * 25.0
+        };
+        // clean air is above ~250kOhm, heavily polluted air below ~5kOhm
+        let gas_score = ((gas.max(5_000.0).min(250_000.0) - 5_000.0) / 245_000.0) * 75.0;
+
+        Some((100.0 - humidity_score - gas_score) * 5.0)
+    }
+}
+
+pub trait Sensor: Send {
+    fn read(&mut self) -> Result<Reading, String>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SensorKind {
+    /// BME680 on the i2c bus, address is either 0x76 or 0x77
+    Bme680 { address: u8, settings: Bme680Config },
+    /// DS18B20 or similar 1-wire probe, identified by its id (e.g. `28-0316a2794bff`)
+    OneWire { id: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SensorConfig {
+    pub name: String,
+    pub location: String,
+    pub kind: SensorKind,
+    pub calibration: Calibration,
+}
+
+impl SensorConfig {
+    /// Reads the sensor list from `SENSORS=indoor,outdoor` with one `SENSOR_<NAME>=bme680:0x77`
+    /// or `SENSOR_<NAME>=onewire:<id>` and an optional `SENSOR_<NAME>_LOCATION` per entry.
+    ///
+    /// Without `SENSORS` a single BME680 named by `SENSOR` is used.
+    pub fn from_env() -> Vec<SensorConfig> {
+        let location = dotenv::var("LOCATION").expect("get location name key from .env file");
+
+        let names = match dotenv::var("SENSORS") {
+            Ok(names) => names,
+            Err(_) => {
+                let kind = SensorKind::Bme680 {
+                    address: 0x77,
+                    settings: Bme680Config::from_env(None),
+                };
+                return vec![SensorConfig {
+                    name: dotenv::var("SENSOR").expect("get SENSOR name key from .env file"),
+                    location,
+                    calibration: Calibration::from_env(None, &kind),
+                    kind,
+                }];
+            }
+        };
+
+        names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                let key = format!("SENSOR_{}", name.to_uppercase());
+                let spec =
+                    dotenv::var(&key).unwrap_or_else(|_| panic!("get {} key from .env file", key));
+                let kind = SensorKind::parse(name, &spec)
+                    .unwrap_or_else(|e| panic!("invalid {}: {}", key, e));
+                SensorConfig {
+                    name: name.to_string(),
+                    location: dotenv::var(format!("{}_LOCATION", key))
+                        .unwrap_or_else(|_| location.clone()),
+                    calibration: Calibration::from_env(Some(name), &kind),
+                    kind,
+                }
+            })
+            .collect()
+    }
+
+    /// `DISPLAY_SENSOR`, or the first of the `sensors` without it
+    pub fn display_sensor(sensors: &[SensorConfig]) -> Result<String, String> {
+        Self::choose_display_sensor(dotenv::var("DISPLAY_SENSOR").ok(), sensors)
+    }
+
+    fn choose_display_sensor(
+        name: Option<String>,
+        sensors: &[SensorConfig],
+    ) -> Result<String, String> {
+        let first = sensors
+            .first()
+            .ok_or("SENSORS is empty, configure at least one sensor")?;
+        match name {
+            Some(name) if sensors.iter().any(|config| config.name == name) => Ok(name),
+            Some(name) => Err(format!(
+                "unknown DISPLAY_SENSOR '{}', expected one of {}",
+                name,
+                sensors
+                    .iter()
+                    .map(|config| config.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            None => Ok(first.name.clone()),
+        }
+    }
+}
+
+impl SensorKind {
+    fn parse(name: &str, spec: &str) -> Result<SensorKind, String> {
+        let mut parts = spec.splitn(2, ':');
+        let kind = parts.next().unwrap_or_default().trim().to_lowercase();
+        let arg = parts.next().map(str::trim);
+        match kind.as_str() {
+            "bme680" => {
+                let address = match arg {
+                    None => 0x77,
+                    Some(addr) => u8::from_str_radix(addr.trim_start_matches("0x"), 16)
+                        .map_err(|e| format!("bad i2c address '{}': {}", addr, e))?,
+                };
+                if address != 0x76 && address != 0x77 {
+                    return Err(format!(
+                        "bme680 address must be 0x76 or 0x77, not {:#x}",
+                        address
+                    ));
+                }
+                Ok(SensorKind::Bme680 {
+                    address,
+                    settings: Bme680Config::from_env(Some(name)),
+                })
+            }
+            "onewire" => match arg {
+                Some(id) if !id.is_empty() => Ok(SensorKind::OneWire { id: id.to_string() }),
+                _ => Err("onewire needs a device id, e.g. 'onewire:28-0316a2794bff'".into()),
+            },
+            _ => Err(format!("unknown sensor kind '{}'", kind)),
+        }
+    }
+}
+
+pub struct ConfiguredSensor {
+    pub config: SensorConfig,
+    pub sensor: Box<dyn Sensor>,
+    pub validator: Validator,
+    /// only for sensors on the raspberry pi itself
+    pub self_heating: Option<SelfHeating>,
+}
+
+impl ConfiguredSensor {
+    pub fn new(config: &SensorConfig, sensor: Box<dyn Sensor>) -> Self {
+        Self {
+            config: config.clone(),
+            sensor,
+            validator: Validator::new(VALIDATION.clone()),
+            self_heating: match config.kind {
+                SensorKind::Bme680 { .. } => SELF_HEATING.clone(),
+                SensorKind::OneWire { .. } => None,
+            },
+        }
+    }
+
+    /// Reads, compensates, calibrates and validates a single reading
+    pub fn sample(&mut self) -> Option<Validated> {
+        let raw = match self.sensor.read() {
+            Ok(reading) => reading,
+            Err(e) => {
+                error(&format!("sensor reading of {}", self.config.name), e);
+                return None;
+            }
+        };
+        debug!("Sensor {}: {:?}", self.config.name, raw);
+
+        let raw = match &self.self_heating {
+            Some(self_heating) => self_heating.compensate(raw).unwrap_or_else(|e| {
+                error("self heating compensation", e);
+                raw
+            }),
+            None => raw,
+        };
+
+        let rejected_before = self.validator.rejected;
+        let calibrated = self.config.calibration.apply(raw);
+        let validated = self.validator.validate(calibrated, Instant::now());
+        if self.validator.rejected != rejected_before {
+            warn!("Suspicious reading of {}: {:?}", self.config.name, raw);
+            validation_to_influx(&self.config, &self.validator.rejected);
+        }
+        validated
+    }
+
+    /// A single calibrated reading as `Sample`, without compensation, validation
+    /// or any metrics, e.g. for headless renderings
+    pub fn preview(&mut self) -> Option<Sample> {
+        let latest = self.config.calibration.apply(self.sensor.read().ok()?);
+        Some(Sample {
+            name: self.config.name.clone(),
+            latest,
+            aggregate: Aggregate::of(&[latest]),
+        })
+    }
+}
+
+/// Draws the latest samples of the sampler.
+///
+/// The sensor named by `DISPLAY_SENSOR` is drawn large, the others are listed below it (large layout only).
+pub fn sensor<C: Shade, T: DrawTarget<C>>(display: &mut T, samples: &[Sample]) {
+    let mut others = Vec::new();
+
+    for sample in samples {
+        if sample.name == *DISPLAY_SENSOR {
+            draw_sensor(display, &sample.latest, sample.aggregate.as_ref());
+        } else {
+            others.push((sample.name.as_str(), sample.latest));
+        }
+    }
+
+    #[cfg(feature = "layout_large")]
+    draw_other_sensors(display, &others);
+}
 
 #[cfg(feature = "simulator")]
-pub fn sensor<T: DrawTarget<BinaryColor>>(display: &mut T) -> Result<(), String> {
-    let temp = 15.7;
-    let pressure = 972.44;
-    let humidity = 43.84;
-    let gas = 538138;
+pub struct FakeSensor(pub Reading);
 
-    draw_sensor(display, temp, humidity, pressure, gas);
+#[cfg(feature = "simulator")]
+impl FakeSensor {
+    pub fn new(kind: &SensorKind) -> Self {
+        match kind {
+            SensorKind::Bme680 { .. } => FakeSensor(Reading {
+                temperature: 15.7,
+                humidity: Some(43.84),
+                pressure: Some(972.44),
+                gas_resistance: Some(538138),
+            }),
+            SensorKind::OneWire { .. } => FakeSensor(Reading {
+                temperature: -3.5,
+                humidity: None,
+                pressure: None,
+                gas_resistance: None,
+            }),
+        }
+    }
+}
 
-    Ok(())
+#[cfg(feature = "simulator")]
+impl Sensor for FakeSensor {
+    fn read(&mut self) -> Result<Reading, String> {
+        Ok(self.0)
+    }
 }
 
 #[cfg(not(feature = "simulator"))]
-pub fn sensor<T: DrawTarget<BinaryColor>, I2C, D>(
-    display: &mut T,
-    bme: &mut Bme680<I2C, D>,
-) -> Result<(), String>
+impl<I2C, D> Sensor for Bme680<I2C, D>
 where
     D: DelayMs<u8>,
     I2C: Read + Write,
     <I2C as embedded_hal::blocking::i2c::Read>::Error: std::fmt::Debug,
     <I2C as embedded_hal::blocking::i2c::Write>::Error: std::fmt::Debug,
 {
-    let power_mode = bme
-        .get_sensor_mode()
-        .map_err(|e| format!("Unable to get sensor mode: {:?}", e))?;
-    debug!("Sensor power mode: {:?}", power_mode);
-    debug!("Setting forced power modes");
-    bme.set_sensor_mode(PowerMode::ForcedMode)
-        .map_err(|e| format!("Unable to set sensor mode: {:?}", e))?;
-    debug!("Retrieving sensor data");
-    let (data, _state) = bme
-        .get_sensor_data()
-        .map_err(|e| format!("Unable to get sensor data: {:?}", e))?;
-    debug!("Sensor Data {:?}", data);
-    let temp = data.temperature_celsius();
-    let pressure = data.pressure_hpa();
-    let humidity = data.humidity_percent();
-    let gas = data.gas_resistance_ohm();
-    if humidity >= 99.9 {
-        return Err(format!("Received bad sensor data: {:?}", data));
-    }
-
-    draw_sensor(display, temp, humidity, pressure, gas);
-
-    sensor_to_influx(temp, humidity, pressure, gas);
-
-    Ok(())
-}
-
-#[cfg(feature = "epd4in2")]
-fn draw_sensor<T: DrawTarget<BinaryColor>>(
+    fn read(&mut self) -> Result<Reading, String> {
+        let power_mode = self
+            .get_sensor_mode()
+            .map_err(|e| format!("Unable to get sensor mode: {:?}", e))?;
+        debug!("Sensor power mode: {:?}", power_mode);
+        debug!("Setting forced power modes");
+        self.set_sensor_mode(PowerMode::ForcedMode)
+            .map_err(|e| format!("Unable to set sensor mode: {:?}", e))?;
+        debug!("Retrieving sensor data");
+        let (data, _state) = self
+            .get_sensor_data()
+            .map_err(|e| format!("Unable to get sensor data: {:?}", e))?;
+        debug!("Sensor Data {:?}", data);
+
+        Ok(Reading {
+            temperature: data.temperature_celsius(),
+            humidity: Some(data.humidity_percent()),
+            pressure: Some(data.pressure_hpa()),
+            gas_resistance: Some(data.gas_resistance_ohm()),
+        })
+    }
+}
+
+fn sensor_details(reading: &Reading) -> String {
+    let mut lines = Vec::new();
+    if let Some(gas_resistance) = reading.gas_resistance {
+        lines.push(format!(
+            "{:>7}kOhm",
+            LOCALE.number(gas_resistance as f32 / 1000.0, 2)
+        ));
+    }
+    if let Some(pressure) = reading.pressure {
+        lines.push(format!(
+            "{:>7}{}",
+            LOCALE.number(UNITS.pressure.convert(pressure), 2),
+            UNITS.pressure.symbol()
+        ));
+    }
+    if let Some(humidity) = reading.humidity {
+        lines.push(format!("{:>7}%", LOCALE.number(humidity, 2)));
+    }
+    lines.join("\n")
+}
+
+#[cfg(feature = "layout_large")]
+fn draw_sensor<C: Shade, T: DrawTarget<C>>(
     display: &mut T,
-    temp: f32,
-    humidity: f32,
-    pressure: f32,
-    gas_resistance: u32,
+    reading: &Reading,
+    aggregate: Option<&Aggregate>,
 ) {
-    text_24x32(display, &format!("{:5.1}°C", temp), (0, 100).into());
-    text_8x16(
+    text_aligned(
         display,
-        &format!(
-            "{:7.2}kOhm\n{:7.2}hPa\n{:7.2}%",
-            gas_resistance as f32 / 1000.0,
-            pressure,
-            humidity,
-        ),
-        (0, 0).into(),
+        TextFont::F24x32,
+        &UNITS.format_temperature(reading.temperature, 1),
+        area((0, 100).into(), 7 * 24, 32),
+        HAlign::Left,
+        VAlign::Top,
     );
+    text_8x16(display, &sensor_details(reading), (0, 0).into());
+    if let Some(aggregate) = aggregate {
+        text_6x8(
+            display,
+            &format!(
+                "{:>5} .. {:>5}{}",
+                LOCALE.number(UNITS.temperature(aggregate.min.temperature), 1),
+                LOCALE.number(UNITS.temperature(aggregate.max.temperature), 1),
+                UNITS.temperature.symbol()
+            ),
+            (24, 136).into(),
+        );
+    }
 }
 
-#[cfg(feature = "epd2in9")]
-fn draw_sensor<T: DrawTarget<BinaryColor>>(
+#[cfg(feature = "layout_small")]
+fn draw_sensor<C: Shade, T: DrawTarget<C>>(
     display: &mut T,
-    temp: f32,
-    humidity: f32,
-    pressure: f32,
-    gas_resistance: u32,
+    reading: &Reading,
+    _aggregate: Option<&Aggregate>,
 ) {
-    text_24x32(
-        display,
-        &format!("{:5.1}°", temp),
-        (0, height() - 32).into(),
+    let text = format!(
+        "{}°",
+        LOCALE.number(UNITS.temperature(reading.temperature), 1)
     );
-    text_8x16(
-        display,
-        &format!(
-            "{:7.2}kOhm\n{:7.2}hPa\n{:7.2}%",
-            gas_resistance as f32 / 1000.0,
-            pressure,
-            humidity,
-        ),
-        (0, 0).into(),
+    let font = if narrow() {
+        TextFont::F12x16
+    } else {
+        TextFont::F24x32
+    };
+    let bounds = area(
+        (0, height() - font.line_height()).into(),
+        width() / 2,
+        font.line_height(),
     );
+    text_aligned(display, font, &text, bounds, HAlign::Left, VAlign::Top);
+    if narrow() {
+        text_6x8(display, &sensor_details(reading), (0, 0).into());
+    } else {
+        text_8x16(display, &sensor_details(reading), (0, 0).into());
+    }
+}
+
+#[cfg(feature = "layout_large")]
+fn draw_other_sensors<C: Shade, T: DrawTarget<C>>(display: &mut T, others: &[(&str, Reading)]) {
+    // fits between the sensor details and the big temperature
+    for (i, (name, reading)) in others.iter().take(5).enumerate() {
+        text_6x8(
+            display,
+            &format!(
+                "{:8.8} {:>7}",
+                name,
+                UNITS.format_temperature(reading.temperature, 1)
+            ),
+            (0, 52 + 9 * i as i32).into(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(name: &str) -> SensorConfig {
+        let kind = SensorKind::OneWire {
+            id: "28-0316a2794bff".into(),
+        };
+        SensorConfig {
+            name: name.into(),
+            location: "test".into(),
+            calibration: Calibration {
+                temperature: Linear::Offset(0.0),
+                humidity: Linear::Offset(0.0),
+                pressure: Linear::Offset(0.0),
+            },
+            kind,
+        }
+    }
+
+    #[test]
+    fn sensor_kinds() {
+        assert_eq!(
+            SensorKind::parse("outdoor", "onewire:28-0316a2794bff"),
+            Ok(SensorKind::OneWire {
+                id: "28-0316a2794bff".into()
+            })
+        );
+        assert!(SensorKind::parse("outdoor", "onewire").is_err());
+        assert!(SensorKind::parse("indoor", "bme680:0x42").is_err());
+        assert!(SensorKind::parse("indoor", "dht22:4").is_err());
+    }
+
+    #[test]
+    fn display_sensor_defaults_to_the_first() {
+        let sensors = [config("indoor"), config("outdoor")];
+        assert_eq!(
+            SensorConfig::choose_display_sensor(None, &sensors),
+            Ok("indoor".to_string())
+        );
+        assert_eq!(
+            SensorConfig::choose_display_sensor(Some("outdoor".into()), &sensors),
+            Ok("outdoor".to_string())
+        );
+    }
+
+    #[test]
+    fn unknown_display_sensor() {
+        let sensors = [config("indoor"), config("outdoor")];
+        assert_eq!(
+            SensorConfig::choose_display_sensor(Some("indor".into()), &sensors),
+            Err("unknown DISPLAY_SENSOR 'indor', expected one of indoor, outdoor".to_string())
+        );
+        assert!(SensorConfig::choose_display_sensor(None, &[]).is_err());
+        assert!(SensorConfig::choose_display_sensor(Some("indoor".into()), &[]).is_err());
+    }
 }
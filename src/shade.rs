@@ -0,0 +1,51 @@
+use embedded_graphics::pixelcolor::{BinaryColor, Gray2, GrayColor, PixelColor};
+
+/// Colors the screens are drawn with, black/white panels just lack the grays
+pub trait Shade: PixelColor {
+    fn black() -> Self;
+    fn white() -> Self;
+    /// for grid lines, shaded areas and smoothed fonts, `None` without grayscale
+    fn light() -> Option<Self>;
+    /// 0 is black, 255 white
+    fn brightness(self) -> u8;
+}
+
+impl Shade for BinaryColor {
+    fn black() -> Self {
+        BinaryColor::On
+    }
+
+    fn white() -> Self {
+        BinaryColor::Off
+    }
+
+    fn light() -> Option<Self> {
+        None
+    }
+
+    fn brightness(self) -> u8 {
+        match self {
+            BinaryColor::On => 0x00,
+            BinaryColor::Off => 0xff,
+        }
+    }
+}
+
+/// 4 levels, e.g. for panels with a grayscale lut
+impl Shade for Gray2 {
+    fn black() -> Self {
+        Gray2::BLACK
+    }
+
+    fn white() -> Self {
+        Gray2::WHITE
+    }
+
+    fn light() -> Option<Self> {
+        Some(Gray2::new(2))
+    }
+
+    fn brightness(self) -> u8 {
+        self.luma() * 0x55
+    }
+}
@@ -0,0 +1,43 @@
+use crate::*;
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::sync::{Mutex, MutexGuard};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorState {
+    pub time: DateTime<Local>,
+    pub message: String,
+}
+
+/// What is currently shown on the display and how it went
+#[derive(Debug, Default, Serialize)]
+pub struct State {
+    pub samples: Vec<Sample>,
+    pub weather: Option<Weather>,
+    pub forecast: Option<Vec<ForecastSlot>>,
+    pub last_refresh: Option<DateTime<Local>>,
+    pub last_error: Option<ErrorState>,
+    pub page: Option<Page>,
+    /// PNG of the last frame
+    #[serde(skip)]
+    pub frame: Vec<u8>,
+}
+
+impl State {
+    pub fn refreshed(&mut self, samples: Vec<Sample>, frame: &Framebuffer) {
+        self.samples = samples;
+        self.last_refresh = Some(Local::now());
+        self.frame.clear();
+        if let Err(e) = frame.write_png(&mut self.frame) {
+            log::error!("Unable to encode frame: {}", e);
+        }
+    }
+}
+
+lazy_static! {
+    static ref STATE: Mutex<State> = Mutex::new(State::default());
+}
+
+pub fn state() -> MutexGuard<'static, State> {
+    STATE.lock().unwrap_or_else(|e| e.into_inner())
+}
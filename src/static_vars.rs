@@ -1,31 +1,40 @@
+use crate::{
+    timezone_from_env, weather_location_from_env, BitmapFont, ClockFont, Coordinates, Locale,
+    Output, Page, SelfHeating, SensorConfig, SensorMetrics, TileConfig, TimeFormat, Tz, Units,
+    ValidationConfig,
+};
+use core::time::Duration;
 use dotenv;
 use influx_db_client::{Client, Value};
 use lazy_static::lazy_static;
 use openweather::LocationSpecifier;
-use openweather::{Language, Settings, Unit};
+use openweather::{Settings, Unit};
+use std::ffi::OsString;
 // const SETTINGS: &Settings = &Settings {
 //     unit: Some(Unit::Metric),
 //     lang: Some(Language::German),
 // };
 
 lazy_static! {
-    pub static ref WEATHER_LOCATION: LocationSpecifier = {
-        LocationSpecifier::CityAndCountryName {
-            city: dotenv::var("CITY")
-                .expect("get CITY (e.g. 'Tübingen') key from .env file"),
-
-            country: dotenv::var("COUNTRY")
-                .expect("get COUNTRY (e.g. 'DE') key from .env file"),
-        }
-    };
+    /// the environment before the .env was loaded into it, see `relaunch`
+    pub static ref LAUNCH_ENV: Vec<(OsString, OsString)> = std::env::vars_os().collect();
+    pub static ref WEATHER_LOCATION: LocationSpecifier = weather_location_from_env();
+    /// for the sun and moon, `None` without `LATITUDE`/`LONGITUDE`
+    pub static ref COORDINATES: Option<Coordinates> = Coordinates::from_env();
     pub static ref OPENWEATHER_API_KEY: String = {
         let key = "API_KEY";
         dotenv::var(key).expect("get api key from .env file")
     };
+    /// metric like the sensors, `UNITS` only apply when drawing
     pub static ref OPENWEATHER_SETTINGS: Settings = Settings {
         unit: Some(Unit::Metric),
-        lang: Some(Language::German),
+        lang: Some(LOCALE.language()),
     };
+    pub static ref LOCALE: Locale = Locale::from_env();
+    pub static ref TIME_FORMAT: TimeFormat = TimeFormat::from_env();
+    pub static ref TIMEZONE: Tz = timezone_from_env();
+    pub static ref UNITS: Units = Units::from_env();
+    pub static ref METRICS_UNITS: Units = Units::metrics_from_env();
 
 
     pub static ref INFLUX_CLIENT: Client = {
@@ -41,9 +50,54 @@ lazy_static! {
 
         Client::new(addr.parse().unwrap(), db).set_authentication(user, password)
     };
+    /// Device level sensor tag for errors and status, defaults to all configured sensor names
     pub static ref SENSOR: Value = Value::String(
-        dotenv::var("SENSOR").expect("get SENSOR name key from .env file")
+        dotenv::var("SENSOR").unwrap_or_else(|_| {
+            SENSORS
+                .iter()
+                .map(|s| s.name.as_str())
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+    );
+    pub static ref SENSORS: Vec<SensorConfig> = SensorConfig::from_env();
+    pub static ref TILES: Vec<TileConfig> = TileConfig::from_env();
+    pub static ref PAGES: Vec<Page> = Page::rotation_from_env();
+    pub static ref OUTPUT: Output = Output::from_env();
+    pub static ref CLOCK_FONT: ClockFont = ClockFont::from_env();
+    /// BDF font for the labels and details, e.g. a proportional one
+    pub static ref LABEL_FONT: Option<BitmapFont> = dotenv::var("LABEL_FONT").ok().map(|path| {
+        BitmapFont::load(path.as_ref()).unwrap_or_else(|e| panic!("invalid LABEL_FONT {}", e))
+    });
+    /// °C, the current weather at or below is drawn as a warning
+    pub static ref FROST_WARNING: f32 = dotenv::var("FROST_WARNING")
+        .map(|t| t.parse().expect("FROST_WARNING number"))
+        .unwrap_or(0.0);
+    /// the IAQ of the `DISPLAY_SENSOR` at or above is drawn as a warning
+    pub static ref IAQ_WARNING: f32 = dotenv::var("IAQ_WARNING")
+        .map(|t| t.parse().expect("IAQ_WARNING number"))
+        .unwrap_or(200.0);
+    /// e.g. `0.0.0.0:8080`, the http api is off without it
+    pub static ref HTTP_BIND: Option<String> = dotenv::var("HTTP_BIND").ok();
+    /// needed for commands over the http api
+    pub static ref HTTP_TOKEN: Option<String> = dotenv::var("HTTP_TOKEN").ok();
+    /// not prefixed with `SENSOR_`, that is where the `SENSOR_<NAME>` entries are,
+    /// at least a second so the sampler doesn't spin
+    pub static ref SAMPLE_INTERVAL: Duration = Duration::from_secs(
+        dotenv::var("SAMPLE_INTERVAL")
+            .map(|secs| match secs.parse() {
+                Ok(secs) if secs >= 1 => secs,
+                _ => panic!("invalid SAMPLE_INTERVAL '{}', expected at least 1 second", secs),
+            })
+            .unwrap_or(60)
     );
+    pub static ref METRICS_WINDOW: SensorMetrics = SensorMetrics::from_env();
+    pub static ref SELF_HEATING: Option<SelfHeating> = SelfHeating::from_env();
+    pub static ref VALIDATION: ValidationConfig = ValidationConfig::from_env();
+    /// Sensor shown with the large font, defaults to the first configured one,
+    /// an empty `SENSORS` or unknown name is rejected by `check_config` before anything is drawn
+    pub static ref DISPLAY_SENSOR: String =
+        SensorConfig::display_sensor(&SENSORS).unwrap_or_default();
     pub static ref LOCATION: Value = Value::String(
         dotenv::var("LOCATION").expect("get location name key from .env file"),
     );
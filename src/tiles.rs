@@ -0,0 +1,132 @@
+use crate::*;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// A value the display doesn't measure itself, subscribed via mqtt
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileConfig {
+    pub label: String,
+    pub topic: String,
+    pub unit: String,
+    /// decimals for numeric values, the payload is shown as is otherwise
+    pub decimals: Option<usize>,
+    /// take this field of a json payload instead of the whole payload
+    pub field: Option<String>,
+}
+
+impl TileConfig {
+    /// Reads `TILES=outdoor,price` with `TILE_<NAME>_TOPIC` and the optional
+    /// `TILE_<NAME>_LABEL`, `TILE_<NAME>_UNIT`, `TILE_<NAME>_DECIMALS` and `TILE_<NAME>_FIELD`
+    pub fn from_env() -> Vec<TileConfig> {
+        let names = match dotenv::var("TILES") {
+            Ok(names) => names,
+            Err(_) => return Vec::new(),
+        };
+        names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                let var =
+                    |key: &str| dotenv::var(format!("TILE_{}_{}", name.to_uppercase(), key)).ok();
+                TileConfig {
+                    label: var("LABEL").unwrap_or_else(|| name.to_string()),
+                    topic: var("TOPIC").unwrap_or_else(|| {
+                        panic!("get TILE_{}_TOPIC key from .env file", name.to_uppercase())
+                    }),
+                    unit: var("UNIT").unwrap_or_default(),
+                    decimals: var("DECIMALS")
+                        .map(|d| d.parse().expect("TILE_<NAME>_DECIMALS number")),
+                    field: var("FIELD"),
+                }
+            })
+            .collect()
+    }
+
+    pub fn format(&self, payload: &str) -> String {
+        let value = match &self.field {
+            Some(field) => match serde_json::from_str::<serde_json::Value>(payload) {
+                Ok(json) => match &json[field.as_str()] {
+                    serde_json::Value::String(s) => s.clone(),
+                    serde_json::Value::Null => return "--".into(),
+                    other => other.to_string(),
+                },
+                Err(_) => return "--".into(),
+            },
+            None => payload.trim().to_string(),
+        };
+        match (self.decimals, value.parse::<f64>()) {
+            (Some(decimals), Ok(number)) => {
+                format!("{}{}", LOCALE.number(number, decimals), self.unit)
+            }
+            _ => format!("{}{}", value, self.unit),
+        }
+    }
+}
+
+lazy_static! {
+    /// latest payload per topic
+    static ref TILE_VALUES: Mutex<HashMap<String, String>> = Mutex::new(HashMap::new());
+}
+
+pub(crate) fn tile_message(topic: &str, payload: &[u8]) {
+    let payload = String::from_utf8_lossy(payload).into_owned();
+    TILE_VALUES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(topic.to_string(), payload);
+}
+
+const TILE_WIDTH: i32 = 130;
+const TILE_HEIGHT: i32 = 36;
+
+/// Label in small and the value in large font with a frame around
+pub fn value_tile<C: Shade, T: DrawTarget<C>>(
+    display: &mut T,
+    label: &str,
+    value: &str,
+    top_left: Point,
+) {
+    let bottom_right = top_left + Point::new(TILE_WIDTH - 4, TILE_HEIGHT - 4);
+    let _ = rectangle(top_left, bottom_right).draw(display);
+    for (start, end) in [
+        (top_left, Point::new(bottom_right.x, top_left.y)),
+        (Point::new(bottom_right.x, top_left.y), bottom_right),
+        (bottom_right, Point::new(top_left.x, bottom_right.y)),
+        (Point::new(top_left.x, bottom_right.y), top_left),
+    ]
+    .iter()
+    {
+        let _ = line(*start, *end).draw(display);
+    }
+    let inner = TILE_WIDTH - 4 - 5;
+    for (font, text, y) in [(TextFont::F6x8, label, 2), (TextFont::F12x16, value, 12)].iter() {
+        text_aligned(
+            display,
+            *font,
+            text,
+            area(top_left + Point::new(3, *y), inner, font.line_height()),
+            HAlign::Left,
+            VAlign::Top,
+        );
+    }
+}
+
+/// All configured tiles in a grid, `--` until the first message arrived
+pub fn draw_tiles<C: Shade, T: DrawTarget<C>>(display: &mut T) {
+    let values = TILE_VALUES.lock().unwrap_or_else(|e| e.into_inner());
+    let columns = (width() / TILE_WIDTH).max(1);
+    for (i, tile) in TILES.iter().enumerate() {
+        let value = values
+            .get(&tile.topic)
+            .map_or_else(|| "--".to_string(), |payload| tile.format(payload));
+        let i = i as i32;
+        value_tile(
+            display,
+            &tile.label,
+            &value,
+            Point::new((i % columns) * TILE_WIDTH, (i / columns) * TILE_HEIGHT),
+        );
+    }
+}
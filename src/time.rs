@@ -1,65 +1,158 @@
 use crate::*;
-use embedded_graphics::pixelcolor::BinaryColor;
 use embedded_graphics::DrawTarget;
+use std::fs;
 
-pub fn time<T: DrawTarget<BinaryColor>>(display: &mut T) {
-    let local: DateTime<Local> = Local::now();
+pub trait Clock {
+    fn now(&self) -> DateTime<Local>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+}
+
+/// `TIMEZONE` as IANA name, e.g. `Europe/Berlin`, for the clock, sun times and forecast days,
+/// defaults to `TZ` and then the system's `/etc/timezone` or `/etc/localtime`
+pub fn timezone_from_env() -> Tz {
+    if let Ok(name) = dotenv::var("TIMEZONE") {
+        return parse_timezone(&name).unwrap_or_else(|e| panic!("{}", e));
+    }
+    let system = std::env::var("TZ")
+        .ok()
+        .or_else(|| fs::read_to_string("/etc/timezone").ok())
+        .or_else(|| {
+            let link = fs::read_link("/etc/localtime").ok()?;
+            let name = link.to_str()?.split("zoneinfo/").nth(1)?;
+            Some(name.to_string())
+        });
+    match system.map(|name| name.trim().trim_start_matches(':').parse()) {
+        Some(Ok(tz)) => tz,
+        _ => {
+            log::warn!("Unknown system timezone, using UTC, set TIMEZONE");
+            Tz::UTC
+        }
+    }
+}
+
+pub fn parse_timezone(name: &str) -> Result<Tz, String> {
+    name.parse()
+        .map_err(|e| format!("invalid TIMEZONE '{}': {}", name, e))
+}
+
+pub fn time<C: Shade, T: DrawTarget<C>>(display: &mut T, clock: &dyn Clock) {
+    let local = clock.now().with_timezone(&*TIMEZONE);
 
-    draw(display, local);
+    draw_time(display, local);
 }
 
-#[cfg(feature = "epd4in2")]
-fn draw<T: DrawTarget<BinaryColor>>(display: &mut T, local: DateTime<Local>) {
+#[cfg(feature = "layout_large")]
+pub fn draw_time<C: Shade, T: DrawTarget<C>>(display: &mut T, local: DateTime<Tz>) {
     // date and day
     text_8x16(
         display,
         &format!(
-            "{:4}/{:2}/{:2}\n   {:3}/{:3}",
-            local.year(),
-            local.month(),
-            local.day(),
-            daystr(&local.weekday()),
+            "{}\n   {}/{:3}",
+            LOCALE.date(&local),
+            LOCALE.weekday(local.weekday()),
             local.ordinal()
         ),
         (width() - 80i32, 0).into(),
     );
 
     // time
-    text_24x32(
+    let clock = TextFont::Clock;
+    text_aligned(
         display,
-        &format!(
-            //TODO: use 24:36
-            "{:2}:{:02}", //" {:02}s ",
-            local.hour(),
-            local.minute(),
-            //local.second()
-        ),
-        (width() / 2 - 60i32, 40).into(),
+        clock,
+        &TIME_FORMAT.time(&local),
+        area((width() / 2 - 60, 40).into(), 5 * 24, clock.line_height()),
+        HAlign::Center,
+        VAlign::Top,
     );
 }
 
-#[cfg(feature = "epd2in9")]
-fn draw<T: DrawTarget<BinaryColor>>(display: &mut T, local: DateTime<Local>) {
+#[cfg(feature = "layout_small")]
+pub fn draw_time<C: Shade, T: DrawTarget<C>>(display: &mut T, local: DateTime<Tz>) {
     text_8x16(
         display,
         &format!(
-            "{:2}/{:2}\n  {:3}",
-            local.month(),
-            local.day(),
-            daystr(&local.weekday()),
+            "{}\n  {}",
+            LOCALE.short_date(&local),
+            LOCALE.weekday(local.weekday())
         ),
         (width() - 40i32, 0).into(),
     );
 
-    text_24x32(
+    let clock = TextFont::Clock;
+    text_aligned(
         display,
-        &format!(
-            //TODO: use 24:36
-            "{:2}:{:02}", //" {:02}s ",
-            local.hour(),
-            local.minute(),
-            //local.second()
+        clock,
+        &TIME_FORMAT.time(&local),
+        area(
+            (width() / 2 - 60, height() / 3).into(),
+            5 * 24,
+            clock.line_height(),
         ),
-        (width() / 2 - 60i32, height() / 3).into(),
+        HAlign::Center,
+        VAlign::Top,
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::Europe::Berlin;
+
+    fn clock(utc: DateTime<Utc>) -> String {
+        let local = utc.with_timezone(&Berlin);
+        format!(
+            "{} {}",
+            Locale::English.date(&local),
+            TimeFormat::H24.time(&local)
+        )
+    }
+
+    #[test]
+    fn clock_at_spring_forward() {
+        assert_eq!(
+            clock(Utc.ymd(2020, 3, 29).and_hms(0, 59, 0)),
+            "2020/03/29 1:59"
+        );
+        assert_eq!(
+            clock(Utc.ymd(2020, 3, 29).and_hms(1, 0, 0)),
+            "2020/03/29 3:00"
+        );
+        assert_eq!(
+            clock(Utc.ymd(2020, 3, 29).and_hms(22, 0, 0)),
+            "2020/03/30 0:00"
+        );
+    }
+
+    #[test]
+    fn clock_at_fall_back() {
+        assert_eq!(
+            clock(Utc.ymd(2020, 10, 25).and_hms(0, 59, 0)),
+            "2020/10/25 2:59"
+        );
+        assert_eq!(
+            clock(Utc.ymd(2020, 10, 25).and_hms(1, 0, 0)),
+            "2020/10/25 2:00"
+        );
+        assert_eq!(
+            clock(Utc.ymd(2020, 10, 25).and_hms(23, 0, 0)),
+            "2020/10/26 0:00"
+        );
+    }
+
+    #[test]
+    fn timezone_names() {
+        assert_eq!(parse_timezone("Europe/Berlin"), Ok(Berlin));
+        assert_eq!(parse_timezone("UTC"), Ok(Tz::UTC));
+        assert!(parse_timezone("Europe/Tuebingen").is_err());
+        assert!(parse_timezone("CEST").is_err());
+        assert!(parse_timezone("").is_err());
+    }
+}
@@ -0,0 +1,218 @@
+use crate::*;
+
+/// `TEMPERATURE_UNIT=c|f`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TemperatureUnit {
+    Celsius,
+    Fahrenheit,
+}
+
+impl TemperatureUnit {
+    pub fn convert(self, celsius: f32) -> f32 {
+        match self {
+            TemperatureUnit::Celsius => celsius,
+            TemperatureUnit::Fahrenheit => celsius * 9.0 / 5.0 + 32.0,
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            TemperatureUnit::Celsius => "°C",
+            TemperatureUnit::Fahrenheit => "°F",
+        }
+    }
+}
+
+/// `PRESSURE_UNIT=hpa|inhg|mmhg`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PressureUnit {
+    Hectopascal,
+    InchOfMercury,
+    MillimeterOfMercury,
+}
+
+impl PressureUnit {
+    pub fn convert(self, hpa: f32) -> f32 {
+        match self {
+            PressureUnit::Hectopascal => hpa,
+            PressureUnit::InchOfMercury => hpa / 33.863_89,
+            PressureUnit::MillimeterOfMercury => hpa / 1.333_224,
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            PressureUnit::Hectopascal => "hPa",
+            PressureUnit::InchOfMercury => "inHg",
+            PressureUnit::MillimeterOfMercury => "mmHg",
+        }
+    }
+
+    /// About the same resolution for every unit
+    pub fn decimals(self) -> usize {
+        match self {
+            PressureUnit::Hectopascal => 1,
+            PressureUnit::InchOfMercury => 2,
+            PressureUnit::MillimeterOfMercury => 1,
+        }
+    }
+}
+
+/// Units the values are drawn in, sensors, the weather api and thresholds
+/// like `FROST_WARNING` stay metric
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Units {
+    pub temperature: TemperatureUnit,
+    pub pressure: PressureUnit,
+}
+
+impl Units {
+    pub const METRIC: Units = Units {
+        temperature: TemperatureUnit::Celsius,
+        pressure: PressureUnit::Hectopascal,
+    };
+
+    pub fn from_env() -> Self {
+        let temperature = match dotenv::var("TEMPERATURE_UNIT").as_ref().map(String::as_str) {
+            Ok("c") | Err(_) => TemperatureUnit::Celsius,
+            Ok("f") => TemperatureUnit::Fahrenheit,
+            Ok(unit) => panic!("invalid TEMPERATURE_UNIT '{}', expected c or f", unit),
+        };
+        let pressure = match dotenv::var("PRESSURE_UNIT").as_ref().map(String::as_str) {
+            Ok("hpa") | Err(_) => PressureUnit::Hectopascal,
+            Ok("inhg") => PressureUnit::InchOfMercury,
+            Ok("mmhg") => PressureUnit::MillimeterOfMercury,
+            Ok(unit) => panic!(
+                "invalid PRESSURE_UNIT '{}', expected hpa, inhg or mmhg",
+                unit
+            ),
+        };
+        Self {
+            temperature,
+            pressure,
+        }
+    }
+
+    /// `METRICS_UNITS=metric|display` for influx and mqtt, metric by default
+    /// so existing series don't change
+    pub fn metrics_from_env() -> Self {
+        match dotenv::var("METRICS_UNITS").as_ref().map(String::as_str) {
+            Ok("metric") | Err(_) => Units::METRIC,
+            Ok("display") => *UNITS,
+            Ok(units) => panic!(
+                "invalid METRICS_UNITS '{}', expected metric or display",
+                units
+            ),
+        }
+    }
+
+    pub fn temperature(&self, celsius: f32) -> f32 {
+        self.temperature.convert(celsius)
+    }
+
+    /// e.g. `21.5°C`, with the decimal separator of the `LOCALE`
+    pub fn format_temperature(&self, celsius: f32, decimals: usize) -> String {
+        format!(
+            "{}{}",
+            LOCALE.number(self.temperature(celsius), decimals),
+            self.temperature.symbol()
+        )
+    }
+
+    /// e.g. `1013.2hPa` or `29.92inHg`
+    pub fn format_pressure(&self, hpa: f32) -> String {
+        format!(
+            "{}{}",
+            LOCALE.number(self.pressure.convert(hpa), self.pressure.decimals()),
+            self.pressure.symbol()
+        )
+    }
+
+    /// Humidity and gas resistance have no alternative units
+    pub fn convert(&self, reading: &Reading) -> Reading {
+        Reading {
+            temperature: self.temperature(reading.temperature),
+            pressure: reading.pressure.map(|hpa| self.pressure.convert(hpa)),
+            ..*reading
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(value: f32, expected: f32) {
+        assert!(
+            (value - expected).abs() < 0.01,
+            "{} is not {}",
+            value,
+            expected
+        );
+    }
+
+    #[test]
+    fn celsius_to_fahrenheit() {
+        assert_close(TemperatureUnit::Celsius.convert(21.5), 21.5);
+        assert_close(TemperatureUnit::Fahrenheit.convert(0.0), 32.0);
+        assert_close(TemperatureUnit::Fahrenheit.convert(21.5), 70.7);
+        assert_close(TemperatureUnit::Fahrenheit.convert(-10.0), 14.0);
+        assert_close(TemperatureUnit::Fahrenheit.convert(-40.0), -40.0);
+    }
+
+    #[test]
+    fn hectopascal_to_mercury() {
+        assert_close(PressureUnit::Hectopascal.convert(1013.25), 1013.25);
+        assert_close(PressureUnit::InchOfMercury.convert(1013.25), 29.92);
+        assert_close(PressureUnit::MillimeterOfMercury.convert(1013.25), 760.0);
+    }
+
+    #[test]
+    fn symbols() {
+        assert_eq!(TemperatureUnit::Celsius.symbol(), "°C");
+        assert_eq!(TemperatureUnit::Fahrenheit.symbol(), "°F");
+        assert_eq!(PressureUnit::Hectopascal.symbol(), "hPa");
+        assert_eq!(PressureUnit::InchOfMercury.symbol(), "inHg");
+        assert_eq!(PressureUnit::MillimeterOfMercury.symbol(), "mmHg");
+    }
+
+    #[test]
+    fn format_temperature() {
+        // wins over the .env, no other test reads the locale
+        std::env::set_var("LOCALE", "en");
+        let fahrenheit = Units {
+            temperature: TemperatureUnit::Fahrenheit,
+            ..Units::METRIC
+        };
+        assert_eq!(Units::METRIC.format_temperature(21.46, 1), "21.5°C");
+        assert_eq!(Units::METRIC.format_temperature(-3.6, 0), "-4°C");
+        assert_eq!(fahrenheit.format_temperature(21.5, 1), "70.7°F");
+        assert_eq!(fahrenheit.format_temperature(-40.0, 0), "-40°F");
+    }
+
+    #[test]
+    fn convert_reading() {
+        let units = Units {
+            temperature: TemperatureUnit::Fahrenheit,
+            pressure: PressureUnit::InchOfMercury,
+        };
+        let reading = Reading {
+            temperature: 20.0,
+            humidity: Some(43.8),
+            pressure: Some(1013.25),
+            gas_resistance: Some(538_138),
+        };
+        let converted = units.convert(&reading);
+        assert_close(converted.temperature, 68.0);
+        assert_close(converted.pressure.unwrap(), 29.92);
+        assert_eq!(converted.humidity, reading.humidity);
+        assert_eq!(converted.gas_resistance, reading.gas_resistance);
+
+        let without_pressure = Reading {
+            pressure: None,
+            ..reading
+        };
+        assert_eq!(units.convert(&without_pressure).pressure, None);
+        assert_eq!(Units::METRIC.convert(&reading), reading);
+    }
+}
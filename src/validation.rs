@@ -0,0 +1,406 @@
+use crate::*;
+use std::collections::VecDeque;
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Quantity {
+    Temperature,
+    Humidity,
+    Pressure,
+    GasResistance,
+}
+
+impl Quantity {
+    pub const ALL: [Quantity; 4] = [
+        Quantity::Temperature,
+        Quantity::Humidity,
+        Quantity::Pressure,
+        Quantity::GasResistance,
+    ];
+
+    /// Influx field name, `gasresistence` matches the existing series
+    pub fn name(self) -> &'static str {
+        match self {
+            Quantity::Temperature => "temperature",
+            Quantity::Humidity => "humidity",
+            Quantity::Pressure => "pressure",
+            Quantity::GasResistance => "gasresistence",
+        }
+    }
+
+    /// Part of the `VALID_<KEY>` and `MAX_RATE_<KEY>` variables
+    fn env_key(self) -> &'static str {
+        match self {
+            Quantity::Temperature => "TEMPERATURE",
+            Quantity::Humidity => "HUMIDITY",
+            Quantity::Pressure => "PRESSURE",
+            Quantity::GasResistance => "GAS_RESISTANCE",
+        }
+    }
+
+    pub(crate) fn get(self, reading: &Reading) -> Option<f32> {
+        match self {
+            Quantity::Temperature => Some(reading.temperature),
+            Quantity::Humidity => reading.humidity,
+            Quantity::Pressure => reading.pressure,
+            Quantity::GasResistance => reading.gas_resistance.map(|g| g as f32),
+        }
+    }
+
+    pub(crate) fn set(self, reading: &mut Reading, value: f32) {
+        match self {
+            Quantity::Temperature => reading.temperature = value,
+            Quantity::Humidity => reading.humidity = Some(value),
+            Quantity::Pressure => reading.pressure = Some(value),
+            Quantity::GasResistance => reading.gas_resistance = Some(value as u32),
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Plausible range of a quantity and the max change per minute
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    pub min: f32,
+    pub max: f32,
+    pub max_rate: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Smoothing {
+    None,
+    /// median of the last n plausible values
+    Median(usize),
+    /// exponential moving average with the given weight for the newest value
+    Ema(f32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValidationMode {
+    /// keep suspicious readings but mark them as such
+    Flag,
+    /// don't draw or store suspicious readings at all
+    Drop,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationConfig {
+    pub limits: [Limits; 4],
+    pub smoothing: Smoothing,
+    pub mode: ValidationMode,
+}
+
+impl ValidationConfig {
+    /// Reads `VALID_<QUANTITY>=min:max`, `MAX_RATE_<QUANTITY>` (per minute),
+    /// `SMOOTHING=none|median:<n>|ema:<alpha>` and `VALIDATION_MODE=flag|drop`
+    pub fn from_env() -> Self {
+        let defaults = [
+            (-40.0, 85.0),
+            // the bme680 reports 100% when something went wrong
+            (0.0, 99.9),
+            (300.0, 1100.0),
+            (0.0, 10_000_000.0),
+        ];
+
+        let mut limits = [Limits {
+            min: 0.0,
+            max: 0.0,
+            max_rate: None,
+        }; 4];
+        for q in Quantity::ALL.iter() {
+            let (min, max) = defaults[q.index()];
+            let key = q.env_key();
+            let range_key = format!("VALID_{}", key);
+            let (min, max) = match dotenv::var(&range_key) {
+                Ok(range) => {
+                    parse_range(&range).unwrap_or_else(|e| panic!("invalid {}: {}", range_key, e))
+                }
+                Err(_) => (min, max),
+            };
+            let rate_key = format!("MAX_RATE_{}", key);
+            let max_rate = dotenv::var(&rate_key).ok().map(|rate| {
+                rate.parse()
+                    .unwrap_or_else(|e| panic!("invalid {}: {}", rate_key, e))
+            });
+            limits[q.index()] = Limits { min, max, max_rate };
+        }
+
+        let smoothing = match dotenv::var("SMOOTHING") {
+            Ok(s) => parse_smoothing(&s).unwrap_or_else(|e| panic!("invalid SMOOTHING: {}", e)),
+            Err(_) => Smoothing::None,
+        };
+
+        let mode = match dotenv::var("VALIDATION_MODE").as_deref() {
+            Ok("drop") => ValidationMode::Drop,
+            Ok("flag") | Err(_) => ValidationMode::Flag,
+            Ok(other) => panic!("invalid VALIDATION_MODE: {}", other),
+        };
+
+        Self {
+            limits,
+            smoothing,
+            mode,
+        }
+    }
+}
+
+fn parse_range(range: &str) -> Result<(f32, f32), String> {
+    let mut parts = range.splitn(2, ':');
+    let mut next = || -> Result<f32, String> {
+        parts
+            .next()
+            .ok_or_else(|| format!("expected min:max, got '{}'", range))?
+            .trim()
+            .parse()
+            .map_err(|e| format!("{}", e))
+    };
+    let (min, max) = (next()?, next()?);
+    if min > max {
+        return Err(format!("min {} is bigger than max {}", min, max));
+    }
+    Ok((min, max))
+}
+
+fn parse_smoothing(s: &str) -> Result<Smoothing, String> {
+    let mut parts = s.splitn(2, ':');
+    match (parts.next().unwrap_or_default(), parts.next()) {
+        ("none", None) => Ok(Smoothing::None),
+        ("median", Some(n)) => match n.parse() {
+            Ok(n) if n > 0 => Ok(Smoothing::Median(n)),
+            _ => Err(format!("bad median window '{}'", n)),
+        },
+        ("ema", Some(alpha)) => match alpha.parse() {
+            Ok(alpha) if alpha > 0.0 && alpha <= 1.0 => Ok(Smoothing::Ema(alpha)),
+            _ => Err(format!("bad ema weight '{}', must be in (0, 1]", alpha)),
+        },
+        _ => Err(format!("unknown smoothing '{}'", s)),
+    }
+}
+
+/// Result of a plausible or flagged reading
+#[derive(Debug, Clone, PartialEq)]
+pub struct Validated {
+    pub reading: Reading,
+    /// quantities which failed the range or rate of change check
+    pub suspicious: Vec<Quantity>,
+}
+
+/// Range and rate checks and smoothing for the readings of a single sensor
+pub struct Validator {
+    config: ValidationConfig,
+    last: [Option<(f32, Instant)>; 4],
+    history: [VecDeque<f32>; 4],
+    ema: [Option<f32>; 4],
+    /// suspicious values per quantity since startup
+    pub rejected: [u64; 4],
+}
+
+impl Validator {
+    pub fn new(config: ValidationConfig) -> Self {
+        Self {
+            config,
+            last: [None; 4],
+            history: Default::default(),
+            ema: [None; 4],
+            rejected: [0; 4],
+        }
+    }
+
+    /// Returns `None` if the reading is suspicious and the mode is `ValidationMode::Drop`
+    pub fn validate(&mut self, raw: Reading, now: Instant) -> Option<Validated> {
+        let mut reading = raw;
+        let mut suspicious = Vec::new();
+
+        for &q in Quantity::ALL.iter() {
+            let value = match q.get(&raw) {
+                Some(value) => value,
+                None => continue,
+            };
+            let i = q.index();
+            let limits = self.config.limits[i];
+
+            let in_range = value >= limits.min && value <= limits.max;
+            let rate_ok = match (limits.max_rate, self.last[i]) {
+                (Some(max_rate), Some((last, at))) => {
+                    let minutes = now.duration_since(at).as_secs_f32() / 60.0;
+                    minutes <= 0.0 || ((value - last) / minutes).abs() <= max_rate
+                }
+                _ => true,
+            };
+
+            if !(in_range && rate_ok) {
+                self.rejected[i] += 1;
+                suspicious.push(q);
+                continue;
+            }
+
+            self.last[i] = Some((value, now));
+            q.set(&mut reading, self.smooth(i, value));
+        }
+
+        if !suspicious.is_empty() && self.config.mode == ValidationMode::Drop {
+            return None;
+        }
+        Some(Validated {
+            reading,
+            suspicious,
+        })
+    }
+
+    fn smooth(&mut self, i: usize, value: f32) -> f32 {
+        match self.config.smoothing {
+            Smoothing::None => value,
+            Smoothing::Median(n) => {
+                let history = &mut self.history[i];
+                history.push_back(value);
+                while history.len() > n {
+                    history.pop_front();
+                }
+                let mut sorted: Vec<f32> = history.iter().copied().collect();
+                sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(core::cmp::Ordering::Equal));
+                sorted[sorted.len() / 2]
+            }
+            Smoothing::Ema(alpha) => {
+                let ema = match self.ema[i] {
+                    Some(prev) => alpha * value + (1.0 - alpha) * prev,
+                    None => value,
+                };
+                self.ema[i] = Some(ema);
+                ema
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn config(smoothing: Smoothing, mode: ValidationMode) -> ValidationConfig {
+        let limits = Limits {
+            min: -40.0,
+            max: 85.0,
+            max_rate: Some(1.0),
+        };
+        ValidationConfig {
+            limits: [
+                limits,
+                Limits {
+                    min: 0.0,
+                    max: 99.9,
+                    max_rate: None,
+                },
+                Limits {
+                    min: 300.0,
+                    max: 1100.0,
+                    max_rate: None,
+                },
+                Limits {
+                    min: 0.0,
+                    max: 10_000_000.0,
+                    max_rate: None,
+                },
+            ],
+            smoothing,
+            mode,
+        }
+    }
+
+    fn reading(temperature: f32) -> Reading {
+        Reading {
+            temperature,
+            humidity: None,
+            pressure: None,
+            gas_resistance: None,
+        }
+    }
+
+    fn temperature(validated: Option<Validated>) -> f32 {
+        validated.unwrap().reading.temperature
+    }
+
+    #[test]
+    fn range_includes_both_bounds() {
+        let start = Instant::now();
+        for &t in [-40.0, 0.0, 85.0].iter() {
+            let mut validator = Validator::new(config(Smoothing::None, ValidationMode::Flag));
+            let validated = validator.validate(reading(t), start).unwrap();
+            assert!(validated.suspicious.is_empty(), "{} is in range", t);
+        }
+        for &t in [-40.1, 85.1].iter() {
+            let mut validator = Validator::new(config(Smoothing::None, ValidationMode::Flag));
+            let validated = validator.validate(reading(t), start).unwrap();
+            assert_eq!(validated.suspicious, vec![Quantity::Temperature]);
+            assert_eq!(validator.rejected, [1, 0, 0, 0]);
+        }
+    }
+
+    #[test]
+    fn rate_of_change() {
+        let start = Instant::now();
+        let mut validator = Validator::new(config(Smoothing::None, ValidationMode::Flag));
+        validator.validate(reading(20.0), start);
+
+        let jump = validator.validate(reading(25.0), start + Duration::from_secs(60));
+        assert_eq!(jump.unwrap().suspicious, vec![Quantity::Temperature]);
+
+        // the jump didn't move the reference value, 2 degrees in 2 minutes is fine
+        let drift = validator.validate(reading(22.0), start + Duration::from_secs(120));
+        assert!(drift.unwrap().suspicious.is_empty());
+    }
+
+    #[test]
+    fn flag_keeps_and_drop_discards() {
+        let start = Instant::now();
+        let mut flag = Validator::new(config(Smoothing::None, ValidationMode::Flag));
+        let flagged = flag.validate(reading(100.0), start).unwrap();
+        assert_eq!(flagged.reading, reading(100.0));
+        assert_eq!(flagged.suspicious, vec![Quantity::Temperature]);
+
+        let mut drop = Validator::new(config(Smoothing::None, ValidationMode::Drop));
+        assert_eq!(drop.validate(reading(100.0), start), None);
+        assert_eq!(drop.rejected, [1, 0, 0, 0]);
+        assert_eq!(temperature(drop.validate(reading(20.0), start)), 20.0);
+    }
+
+    #[test]
+    fn median_smoothing() {
+        let start = Instant::now();
+        let mut config = config(Smoothing::Median(3), ValidationMode::Flag);
+        config.limits[0].max_rate = None;
+        let mut validator = Validator::new(config);
+        let smoothed: Vec<f32> = [20.0, 30.0, 21.0, 22.0, 23.0]
+            .iter()
+            .map(|&t| temperature(validator.validate(reading(t), start)))
+            .collect();
+        assert_eq!(smoothed, vec![20.0, 30.0, 21.0, 22.0, 22.0]);
+    }
+
+    #[test]
+    fn ema_smoothing() {
+        let start = Instant::now();
+        let mut config = config(Smoothing::Ema(0.5), ValidationMode::Flag);
+        config.limits[0].max_rate = None;
+        let mut validator = Validator::new(config);
+        let smoothed: Vec<f32> = [20.0, 22.0, 22.0, 10.0]
+            .iter()
+            .map(|&t| temperature(validator.validate(reading(t), start)))
+            .collect();
+        assert_eq!(smoothed, vec![20.0, 21.0, 21.5, 15.75]);
+    }
+
+    #[test]
+    fn parses_ranges_and_smoothing() {
+        assert_eq!(parse_range("-10:40"), Ok((-10.0, 40.0)));
+        assert!(parse_range("40:-10").is_err());
+        assert!(parse_range("40").is_err());
+        assert_eq!(parse_smoothing("median:5"), Ok(Smoothing::Median(5)));
+        assert_eq!(parse_smoothing("ema:0.3"), Ok(Smoothing::Ema(0.3)));
+        assert!(parse_smoothing("median:0").is_err());
+        assert!(parse_smoothing("ema:1.5").is_err());
+    }
+}
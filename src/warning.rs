@@ -0,0 +1,54 @@
+use crate::*;
+
+/// Conditions worth highlighting, tri-color panels draw them in red
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Warning {
+    /// the current weather is at or below `FROST_WARNING`
+    Frost,
+    /// the IAQ of the `DISPLAY_SENSOR` is at or above `IAQ_WARNING`
+    PoorAir,
+}
+
+impl Warning {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Warning::Frost => "FROST",
+            Warning::PoorAir => "AIR",
+        }
+    }
+
+    pub fn active(weather: Option<&Weather>, samples: &[Sample]) -> Vec<Warning> {
+        let mut warnings = Vec::new();
+        if weather.map_or(false, |w| w.temperature <= *FROST_WARNING) {
+            warnings.push(Warning::Frost);
+        }
+        if samples
+            .iter()
+            .filter(|s| s.name == *DISPLAY_SENSOR)
+            .filter_map(|s| s.latest.iaq())
+            .any(|iaq| iaq >= *IAQ_WARNING)
+        {
+            warnings.push(Warning::PoorAir);
+        }
+        warnings
+    }
+}
+
+/// Labels in the space the overview leaves free
+pub fn draw_warnings<C: Shade, T: DrawTarget<C>>(display: &mut T, warnings: &[Warning]) {
+    for (i, warning) in warnings.iter().enumerate() {
+        text_8x16(display, warning.label(), warning_position(i as i32));
+    }
+}
+
+#[cfg(feature = "layout_large")]
+fn warning_position(i: i32) -> Point {
+    // below the date, right of the clock
+    (width() - 80, 48 + 16 * i).into()
+}
+
+#[cfg(feature = "layout_small")]
+fn warning_position(i: i32) -> Point {
+    // below the sensor details, left of the clock
+    (0, 52 + 16 * i).into()
+}
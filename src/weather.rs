@@ -1,38 +1,121 @@
-use embedded_graphics::{pixelcolor::BinaryColor, DrawTarget};
+use embedded_graphics::DrawTarget;
 // use epd_waveshare::prelude::*;
 use crate::*;
 use log::*;
+use serde::{Deserialize, Serialize};
 
-pub fn weather<T: DrawTarget<BinaryColor>>(display: &mut T) {
-    debug!("Weather report current");
-    let weather = match openweather::get_current_weather(
-        &WEATHER_LOCATION,
-        &OPENWEATHER_API_KEY,
-        &OPENWEATHER_SETTINGS,
-    ) {
+/// The parts of the current weather report that are drawn
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Weather {
+    pub temperature: f32,
+    pub sunrise: i64,
+    pub sunset: i64,
+}
+
+/// A 3 hour slot of the 5 day forecast
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ForecastSlot {
+    /// unix timestamp of the slot's start, scenarios without it count as one day
+    #[serde(default)]
+    pub time: i64,
+    pub temp: f32,
+    pub temp_min: f32,
+    pub temp_max: f32,
+}
+
+impl ForecastSlot {
+    /// Day of the slot in the `TIMEZONE`
+    pub fn date(&self) -> NaiveDate {
+        self.date_in(&TIMEZONE)
+    }
+
+    pub fn date_in(&self, timezone: &Tz) -> NaiveDate {
+        Utc.timestamp(self.time, 0)
+            .with_timezone(timezone)
+            .naive_local()
+            .date()
+    }
+}
+
+pub trait WeatherProvider {
+    fn current(&mut self) -> Result<Weather, String>;
+    fn forecast(&mut self) -> Result<Vec<ForecastSlot>, String>;
+}
+
+/// The OpenWeatherMap api configured in the .env file
+pub struct OpenWeather;
+
+impl WeatherProvider for OpenWeather {
+    fn current(&mut self) -> Result<Weather, String> {
+        debug!("Weather report current");
+        let weather = openweather::get_current_weather(
+            &WEATHER_LOCATION,
+            &OPENWEATHER_API_KEY,
+            &OPENWEATHER_SETTINGS,
+        )
+        .map_err(|e| e.to_string())?;
+        info!(
+            "In {}, {} it is {}°C",
+            weather.name, weather.sys.country, weather.main.temp
+        );
+
+        Ok(Weather {
+            temperature: weather.main.temp,
+            sunrise: weather.sys.sunrise as i64,
+            sunset: weather.sys.sunset as i64,
+        })
+    }
+
+    fn forecast(&mut self) -> Result<Vec<ForecastSlot>, String> {
+        let forecast = openweather::get_5_day_forecast(
+            &WEATHER_LOCATION,
+            &OPENWEATHER_API_KEY,
+            &OPENWEATHER_SETTINGS,
+        )
+        .map_err(|e| e.to_string())?;
+
+        Ok(forecast
+            .list
+            .iter()
+            .map(|h3_slot| ForecastSlot {
+                time: h3_slot.dt as i64,
+                temp: h3_slot.main.temp,
+                temp_min: h3_slot.main.temp_min,
+                temp_max: h3_slot.main.temp_max,
+            })
+            .collect())
+    }
+}
+
+pub fn weather<C: Shade, T: DrawTarget<C>>(display: &mut T, provider: &mut dyn WeatherProvider) {
+    let weather = match provider.current() {
         Ok(weather) => weather,
         Err(e) => {
             error("Getting Weather", e);
             return;
         }
     };
-    info!("In {}, {} it is {}°C", weather.name, weather.sys.country, weather.main.temp);
-    draw_temp(display, weather.main.temp);
+    state().weather = Some(weather);
 
-    #[cfg(feature = "epd4in2")]
-    weather_forecast(display, weather.main.temp);
+    #[cfg(feature = "layout_large")]
+    weather_forecast(display, provider, weather.temperature);
 
-    sunrise_and_sunset(
-        display,
-        weather.sys.sunrise as i64,
-        weather.sys.sunset as i64,
-    );
+    draw_weather(display, &weather);
 }
 
-fn sunrise_and_sunset<T: DrawTarget<BinaryColor>>(display: &mut T, sunrise: i64, sunset: i64) {
-    // Construct a datetime from epoch:
-    let sunrise: DateTime<Local> = Utc.timestamp(sunrise as i64, 0).into();
-    let sunset: DateTime<Local> = Utc.timestamp(sunset as i64, 0).into();
+pub fn draw_weather<C: Shade, T: DrawTarget<C>>(display: &mut T, weather: &Weather) {
+    draw_temp(display, weather.temperature);
+
+    // computed locally by `astronomy` with coordinates, even without the api
+    if COORDINATES.is_none() {
+        sunrise_and_sunset(display, weather.sunrise, weather.sunset);
+    }
+}
+
+fn sunrise_and_sunset<C: Shade, T: DrawTarget<C>>(display: &mut T, sunrise: i64, sunset: i64) {
+    // in the timezone of the location, which isn't necessarily the device's
+    let sunrise = Utc.timestamp(sunrise, 0).with_timezone(&*TIMEZONE);
+    let sunset = Utc.timestamp(sunset, 0).with_timezone(&*TIMEZONE);
     // println!("{}", sunrise.to_rfc2822());
     // println!("{}", sunset.to_rfc2822());
     //assert_eq!(dt.to_rfc2822(), "Fri, 14 Jul 2017 02:40:00 +0000");
@@ -40,58 +123,70 @@ fn sunrise_and_sunset<T: DrawTarget<BinaryColor>>(display: &mut T, sunrise: i64,
     draw_sunset(display, sunrise, sunset);
 }
 
-#[cfg(feature = "epd4in2")]
-fn draw_temp<T: DrawTarget<BinaryColor>>(display: &mut T, temp: f32) {
-    text_24x32(
+#[cfg(feature = "layout_large")]
+fn draw_temp<C: Shade, T: DrawTarget<C>>(display: &mut T, temp: f32) {
+    text_aligned(
         display,
-        &format!("{:5.1}°C", temp),
-        (width() - 7 * 24, 100).into(),
+        TextFont::F24x32,
+        &UNITS.format_temperature(temp, 1),
+        area((width() - 7 * 24, 100).into(), 7 * 24, 32),
+        HAlign::Right,
+        VAlign::Top,
     );
 }
 
-#[cfg(feature = "epd2in9")]
-fn draw_temp<T: DrawTarget<BinaryColor>>(display: &mut T, temp: f32) {
-    text_24x32(
-        display,
-        &format!("{:5.1}°", temp),
-        (width() - 6 * 24, height() - 32).into(),
+#[cfg(feature = "layout_small")]
+fn draw_temp<C: Shade, T: DrawTarget<C>>(display: &mut T, temp: f32) {
+    let text = format!("{}°", LOCALE.number(UNITS.temperature(temp), 1));
+    let font = if narrow() {
+        TextFont::F12x16
+    } else {
+        TextFont::F24x32
+    };
+    let bounds = area(
+        (width() / 2, height() - font.line_height()).into(),
+        width() - width() / 2,
+        font.line_height(),
     );
+    text_aligned(display, font, &text, bounds, HAlign::Right, VAlign::Top);
 }
 
-#[cfg(feature = "epd4in2")]
-fn draw_sunset<T: DrawTarget<BinaryColor>>(
+#[cfg(feature = "layout_large")]
+pub(crate) fn draw_sunset<C: Shade, T: DrawTarget<C>>(
     display: &mut T,
-    sunrise: DateTime<Local>,
-    sunset: DateTime<Local>,
+    sunrise: DateTime<Tz>,
+    sunset: DateTime<Tz>,
 ) {
-    text_12x16(
+    text_aligned(
         display,
+        TextFont::F12x16,
         &format!(
-            "{:2}:{:02} | {:2}:{:02}",
-            sunrise.hour(),
-            sunrise.minute(),
-            sunset.hour(),
-            sunset.minute()
+            "{} | {}",
+            TIME_FORMAT.time(&sunrise),
+            TIME_FORMAT.time(&sunset)
         ),
-        (width() / 2 - 80i32, 0).into(),
+        area((width() / 2 - 80, 0).into(), 160, 16),
+        HAlign::Center,
+        VAlign::Top,
     );
 }
 
-#[cfg(feature = "epd2in9")]
-fn draw_sunset<T: DrawTarget<BinaryColor>>(
+#[cfg(feature = "layout_small")]
+pub(crate) fn draw_sunset<C: Shade, T: DrawTarget<C>>(
     display: &mut T,
-    sunrise: DateTime<Local>,
-    sunset: DateTime<Local>,
+    sunrise: DateTime<Tz>,
+    sunset: DateTime<Tz>,
 ) {
-    text_6x12(
+    text_aligned(
         display,
+        TextFont::F6x12,
         &format!(
-            "{:2}:{:02} | {:2}:{:02}",
-            sunrise.hour(),
-            sunrise.minute(),
-            sunset.hour(),
-            sunset.minute()
+            "{} | {}",
+            TIME_FORMAT.time(&sunrise),
+            TIME_FORMAT.time(&sunset)
         ),
-        (width() / 2 - 40i32, 0).into(),
+        area((width() / 2 - 40, 0).into(), 80, 12),
+        HAlign::Center,
+        VAlign::Top,
     );
 }
@@ -0,0 +1,207 @@
+use crate::try_parse_var;
+use core::fmt;
+
+/// Raspberry Pi models differ in the pins on the header and the i2c bus
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Board {
+    /// every model with the 40 pin header
+    Pi,
+    /// the first Model B with the 26 pin header
+    Pi1Rev1,
+}
+
+/// CE1, CE0, MISO, MOSI and SCLK of spi0, the spidev driver owns them
+const SPI_PINS: [u64; 5] = [7, 8, 9, 10, 11];
+
+impl Board {
+    fn parse(board: &str) -> Option<Self> {
+        match board {
+            "rpi" => Some(Board::Pi),
+            "rpi1-rev1" => Some(Board::Pi1Rev1),
+            _ => None,
+        }
+    }
+
+    /// bcm numbers of the gpios on the header
+    fn gpios(&self) -> Vec<u64> {
+        match self {
+            Board::Pi => (2..=27).collect(),
+            Board::Pi1Rev1 => vec![0, 1, 4, 7, 8, 9, 10, 11, 14, 15, 17, 18, 21, 22, 23, 24, 25],
+        }
+    }
+
+    /// SDA and SCL
+    fn i2c_pins(&self) -> [u64; 2] {
+        match self {
+            Board::Pi => [2, 3],
+            Board::Pi1Rev1 => [0, 1],
+        }
+    }
+
+    fn i2c_device(&self) -> &'static str {
+        match self {
+            Board::Pi => "/dev/i2c-1",
+            Board::Pi1Rev1 => "/dev/i2c-0",
+        }
+    }
+}
+
+/// How pins are accessed
+#[derive(Debug, Clone, PartialEq)]
+pub enum GpioBackend {
+    /// `/sys/class/gpio`, deprecated and needs root
+    Sysfs,
+    /// the gpio character device, e.g. `/dev/gpiochip0`
+    Cdev { chip: String },
+}
+
+/// Why the wiring from the .env can't be used
+#[derive(Debug, Clone, PartialEq)]
+pub enum WiringError {
+    /// a variable with a value that can't be parsed or isn't known
+    Invalid(String),
+    /// pins that aren't on the header, are used twice or by spi or i2c
+    Pins(String),
+}
+
+impl fmt::Display for WiringError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WiringError::Invalid(e) => write!(f, "invalid wiring: {}", e),
+            WiringError::Pins(e) => write!(f, "invalid wiring pins: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for WiringError {}
+
+/// Where the panel and the sensors are connected, pins are bcm numbers
+#[derive(Debug, Clone, PartialEq)]
+pub struct Wiring {
+    pub board: Board,
+    pub gpio: GpioBackend,
+    pub spi_device: String,
+    pub spi_speed: u32,
+    /// `None` if the spi controller drives the chip select of the `spi_device` itself
+    pub cs: Option<u64>,
+    pub busy: u64,
+    pub dc: u64,
+    pub rst: u64,
+    pub i2c_device: String,
+}
+
+impl Wiring {
+    /// `aer` is the wiring this project started with, `waveshare-hat` the e-Paper Driver HAT,
+    /// which has its chip select on CE0 of `/dev/spidev0.0`
+    pub fn preset(board: Board, name: &str) -> Option<Self> {
+        let (cs, busy, dc, rst) = match name {
+            "aer" => (Some(26), 5, 6, 16),
+            "waveshare-hat" => (None, 24, 25, 17),
+            _ => return None,
+        };
+        Some(Self {
+            board,
+            gpio: GpioBackend::Sysfs,
+            spi_device: "/dev/spidev0.0".into(),
+            spi_speed: 4_000_000,
+            cs,
+            busy,
+            dc,
+            rst,
+            i2c_device: board.i2c_device().into(),
+        })
+    }
+
+    /// `BOARD=rpi|rpi1-rev1` and `DISPLAY_WIRING=aer|waveshare-hat` choose the preset,
+    /// `SPI_DEVICE`, `SPI_SPEED`, `PIN_CS` (`spi` for the chip select of the spi controller),
+    /// `PIN_BUSY`, `PIN_DC`, `PIN_RST` and `I2C_DEVICE` override it,
+    /// `GPIO_BACKEND=sysfs|cdev` with `GPIO_CHIP` (defaults to `/dev/gpiochip0`) chooses how pins are accessed
+    pub fn from_env() -> Result<Self, WiringError> {
+        let board = match dotenv::var("BOARD") {
+            Ok(board) => Board::parse(&board).ok_or_else(|| {
+                WiringError::Invalid(format!("BOARD '{}', expected rpi or rpi1-rev1", board))
+            })?,
+            Err(_) => Board::Pi,
+        };
+        let preset = dotenv::var("DISPLAY_WIRING").unwrap_or_else(|_| "aer".into());
+        let default = Self::preset(board, &preset).ok_or_else(|| {
+            WiringError::Invalid(format!(
+                "DISPLAY_WIRING '{}', expected aer or waveshare-hat",
+                preset
+            ))
+        })?;
+
+        let gpio = match dotenv::var("GPIO_BACKEND").as_ref().map(String::as_str) {
+            Ok("sysfs") | Err(_) => default.gpio,
+            Ok("cdev") => GpioBackend::Cdev {
+                chip: dotenv::var("GPIO_CHIP").unwrap_or_else(|_| "/dev/gpiochip0".into()),
+            },
+            Ok(backend) => {
+                return Err(WiringError::Invalid(format!(
+                    "GPIO_BACKEND '{}', expected sysfs or cdev",
+                    backend
+                )))
+            }
+        };
+        let cs = match dotenv::var("PIN_CS").as_ref().map(String::as_str) {
+            Ok("spi") => None,
+            _ => parse_var("PIN_CS")?.map_or(default.cs, Some),
+        };
+
+        let wiring = Self {
+            board,
+            gpio,
+            spi_device: dotenv::var("SPI_DEVICE").unwrap_or(default.spi_device),
+            spi_speed: parse_var("SPI_SPEED")?.unwrap_or(default.spi_speed),
+            cs,
+            busy: parse_var("PIN_BUSY")?.unwrap_or(default.busy),
+            dc: parse_var("PIN_DC")?.unwrap_or(default.dc),
+            rst: parse_var("PIN_RST")?.unwrap_or(default.rst),
+            i2c_device: dotenv::var("I2C_DEVICE").unwrap_or(default.i2c_device),
+        };
+        wiring.validate().map_err(WiringError::Pins)?;
+        Ok(wiring)
+    }
+
+    /// The pins which are used as gpios
+    pub fn pins(&self) -> Vec<(&'static str, u64)> {
+        let mut pins = vec![("busy", self.busy), ("dc", self.dc), ("rst", self.rst)];
+        if let Some(cs) = self.cs {
+            pins.insert(0, ("cs", cs));
+        }
+        pins
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let pins = self.pins();
+        let gpios = self.board.gpios();
+        for (i, (name, pin)) in pins.iter().enumerate() {
+            if !gpios.contains(pin) {
+                return Err(format!(
+                    "{} pin {} isn't on the {:?} header",
+                    name, pin, self.board
+                ));
+            }
+            if SPI_PINS.contains(pin) || self.board.i2c_pins().contains(pin) {
+                return Err(format!("{} pin {} is used by spi or i2c", name, pin));
+            }
+            if let Some((other, _)) = pins[..i].iter().find(|(_, other)| other == pin) {
+                return Err(format!("{} and {} are both on pin {}", other, name, pin));
+            }
+        }
+        if !(100_000..=32_000_000).contains(&self.spi_speed) {
+            return Err(format!(
+                "spi speed {}Hz not in 100kHz..32MHz",
+                self.spi_speed
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn parse_var<T: core::str::FromStr>(key: &str) -> Result<Option<T>, WiringError>
+where
+    T::Err: fmt::Display,
+{
+    try_parse_var(None, key).map_err(WiringError::Invalid)
+}
@@ -0,0 +1,31 @@
+use aer::*;
+
+/// Renders the `Fixture` like `--render-once --fixture`, after an intended
+/// layout change `make update_golden` rewrites the images. Panels without an
+/// image yet are skipped instead of failed.
+#[test]
+fn fixture_matches_golden_image() {
+    let path = format!("{}/golden/{}.pbm", env!("CARGO_MANIFEST_DIR"), panel());
+    let golden = match std::fs::read(&path) {
+        Ok(golden) => golden,
+        Err(e) => {
+            eprintln!(
+                "skipped, {}: {}, create it with make update_golden",
+                path, e
+            );
+            return;
+        }
+    };
+
+    Fixture::pin_env();
+    let mut display: Framebuffer = Framebuffer::new(width() as u32, height() as u32);
+    Fixture::default().draw(&mut display);
+    let mut rendered = Vec::new();
+    display.write_pbm(&mut rendered).unwrap();
+
+    assert!(
+        rendered == golden,
+        "{} differs, compare it with --render-once --fixture",
+        path
+    );
+}